@@ -0,0 +1,191 @@
+//! Trigger-based capture management module
+//!
+//! Installs an opt-in `tabletrace` schema with an audit table and per-table
+//! AFTER ROW triggers that `pg_notify` each change. Everything created here
+//! is removed again by `tabletrace capture uninstall`.
+
+use colored::*;
+use tokio_postgres::{Client, NoTls};
+use tracing::error;
+
+use crate::constants::capture::{CHANNEL, SCHEMA, TRIGGER_NAME, TRUNCATE_TRIGGER_NAME};
+use crate::db::{get_base_tables, quote_ident};
+use crate::display::{print_connected, print_connecting, print_success, print_warning};
+use crate::types::{CaptureAction, CaptureConfig};
+
+/// Largest NOTIFY payload sent inline (PostgreSQL rejects payloads of 8000 bytes or more)
+const MAX_INLINE_PAYLOAD: usize = 7900;
+
+/// Run a capture management command
+pub async fn run_capture(config: CaptureConfig) -> Result<(), Box<dyn std::error::Error>> {
+    print_connecting();
+
+    let conn_str = config.connection.to_connection_string();
+    let (mut client, connection) = tokio_postgres::connect(&conn_str, NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            error!("Connection error: {}", e);
+        }
+    });
+
+    print_connected();
+
+    match config.action {
+        CaptureAction::Install { schema } => install(&mut client, &schema).await,
+        CaptureAction::Uninstall => uninstall(&mut client).await,
+    }
+}
+
+/// Install the audit table, trigger function and per-table triggers
+async fn install(
+    client: &mut Client,
+    schema_filter: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tables: Vec<(String, String)> = get_base_tables(client, schema_filter)
+        .await?
+        .into_iter()
+        .filter(|(schema, _)| schema != SCHEMA)
+        .collect();
+
+    if tables.is_empty() {
+        print_warning("No tables found in database.");
+        return Ok(());
+    }
+
+    let tx = client.transaction().await?;
+    tx.batch_execute(&setup_sql()).await?;
+
+    for (schema, table) in &tables {
+        let relation = format!("{}.{}", quote_ident(schema), quote_ident(table));
+        tx.batch_execute(&format!(
+            "DROP TRIGGER IF EXISTS {trigger} ON {relation};
+             CREATE TRIGGER {trigger} AFTER INSERT OR UPDATE OR DELETE ON {relation}
+                 FOR EACH ROW EXECUTE PROCEDURE {schema}.capture_row();
+             DROP TRIGGER IF EXISTS {truncate_trigger} ON {relation};
+             CREATE TRIGGER {truncate_trigger} BEFORE TRUNCATE ON {relation}
+                 FOR EACH STATEMENT EXECUTE PROCEDURE {schema}.capture_truncate();",
+            trigger = TRIGGER_NAME,
+            truncate_trigger = TRUNCATE_TRIGGER_NAME,
+            relation = relation,
+            schema = SCHEMA,
+        ))
+        .await?;
+        eprintln!("  {} {}.{}", "+".green(), schema, table);
+    }
+
+    tx.commit().await?;
+
+    print_success(&format!(
+        "✓ Capture installed on {} table(s). Watch with '--source triggers'.",
+        tables.len()
+    ));
+    Ok(())
+}
+
+/// Remove every capture trigger and the tabletrace schema
+async fn uninstall(client: &mut Client) -> Result<(), Box<dyn std::error::Error>> {
+    let tx = client.transaction().await?;
+
+    let rows = tx
+        .query(
+            "SELECT DISTINCT n.nspname, c.relname FROM pg_trigger t \
+             JOIN pg_class c ON c.oid = t.tgrelid \
+             JOIN pg_namespace n ON n.oid = c.relnamespace \
+             WHERE t.tgname IN ($1, $2) AND NOT t.tgisinternal \
+             ORDER BY n.nspname, c.relname",
+            &[&TRIGGER_NAME, &TRUNCATE_TRIGGER_NAME],
+        )
+        .await?;
+
+    for row in &rows {
+        let schema: String = row.get(0);
+        let table: String = row.get(1);
+        let relation = format!("{}.{}", quote_ident(&schema), quote_ident(&table));
+        tx.batch_execute(&format!(
+            "DROP TRIGGER IF EXISTS {} ON {relation}; DROP TRIGGER IF EXISTS {} ON {relation};",
+            TRIGGER_NAME,
+            TRUNCATE_TRIGGER_NAME,
+            relation = relation
+        ))
+        .await?;
+        eprintln!("  {} {}.{}", "-".red(), schema, table);
+    }
+
+    tx.batch_execute(&format!("DROP SCHEMA IF EXISTS {} CASCADE", SCHEMA))
+        .await?;
+    tx.commit().await?;
+
+    print_success(&format!(
+        "✓ Capture removed from {} table(s) and schema '{}' dropped.",
+        rows.len(),
+        SCHEMA
+    ));
+    Ok(())
+}
+
+/// SQL creating the capture schema, audit table and trigger function
+fn setup_sql() -> String {
+    format!(
+        r#"
+        CREATE SCHEMA IF NOT EXISTS {schema};
+
+        CREATE TABLE IF NOT EXISTS {schema}.audit_log (
+            id bigserial PRIMARY KEY,
+            captured_at timestamptz NOT NULL DEFAULT clock_timestamp(),
+            txid bigint NOT NULL DEFAULT txid_current(),
+            schema_name text NOT NULL,
+            table_name text NOT NULL,
+            operation text NOT NULL,
+            old_row jsonb,
+            new_row jsonb
+        );
+
+        CREATE OR REPLACE FUNCTION {schema}.capture_row() RETURNS trigger
+        LANGUAGE plpgsql AS $$
+        DECLARE
+            audit_id bigint;
+            old_row jsonb;
+            new_row jsonb;
+            payload text;
+        BEGIN
+            IF TG_OP <> 'INSERT' THEN old_row := to_jsonb(OLD); END IF;
+            IF TG_OP <> 'DELETE' THEN new_row := to_jsonb(NEW); END IF;
+
+            payload := jsonb_build_object(
+                'schema', TG_TABLE_SCHEMA, 'table', TG_TABLE_NAME,
+                'op', TG_OP, 'txid', txid_current(), 'old', old_row, 'new', new_row)::text;
+
+            -- Only rows too large to notify are stored; the watcher reads and deletes them by id
+            IF octet_length(payload) > {max_payload} THEN
+                INSERT INTO {schema}.audit_log (schema_name, table_name, operation, old_row, new_row)
+                VALUES (TG_TABLE_SCHEMA, TG_TABLE_NAME, TG_OP, old_row, new_row)
+                RETURNING id INTO audit_id;
+                payload := jsonb_build_object('id', audit_id)::text;
+            END IF;
+
+            PERFORM pg_notify('{channel}', payload);
+            RETURN NULL;
+        END
+        $$;
+
+        -- Runs before the rows are gone; the row count is the statistics estimate
+        CREATE OR REPLACE FUNCTION {schema}.capture_truncate() RETURNS trigger
+        LANGUAGE plpgsql AS $$
+        DECLARE
+            old_row jsonb;
+        BEGIN
+            SELECT jsonb_build_object('rows', COALESCE(sum(n_live_tup), 0)) INTO old_row
+            FROM pg_stat_user_tables WHERE relid = TG_RELID;
+
+            PERFORM pg_notify('{channel}', jsonb_build_object(
+                'schema', TG_TABLE_SCHEMA, 'table', TG_TABLE_NAME,
+                'op', TG_OP, 'txid', txid_current(), 'old', old_row, 'new', NULL)::text);
+            RETURN NULL;
+        END
+        $$;
+        "#,
+        schema = SCHEMA,
+        channel = CHANNEL,
+        max_payload = MAX_INLINE_PAYLOAD,
+    )
+}
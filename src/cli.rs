@@ -1,11 +1,14 @@
 //! CLI parser module
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use std::env;
 
 use crate::constants::defaults;
 use crate::error::{Result, TableTraceError};
-use crate::types::{ConnectionConfig, WatchConfig};
+use crate::types::{
+    CaptureAction, CaptureConfig, ChangeSource, ConnectionConfig, DisplayTimeZone, EventGrouping,
+    QuerySpec, WatchConfig,
+};
 
 /// Environment variable name for PostgreSQL password
 const PGPASSWORD_ENV: &str = "PGPASSWORD";
@@ -24,24 +27,8 @@ pub struct Cli {
 pub enum Commands {
     /// Watch PostgreSQL tables for changes in real-time
     Watch {
-        /// Preset connection: 'supabase' (local Docker), 'postgres' (local default)
-        #[arg(long)]
-        preset: Option<String>,
-        /// Database host
-        #[arg(short = 'H', long, default_value = defaults::HOST)]
-        host: String,
-        /// Database port
-        #[arg(short = 'P', long, default_value_t = defaults::PORT)]
-        port: u16,
-        /// Database name
-        #[arg(short, long)]
-        database: Option<String>,
-        /// Database user
-        #[arg(short, long, default_value = defaults::USER)]
-        user: String,
-        /// Database password (or use PGPASSWORD environment variable)
-        #[arg(short = 'W', long)]
-        password: Option<String>,
+        #[command(flatten)]
+        connection: ConnectionArgs,
         /// Schema to filter tables (use 'all' for all schemas)
         #[arg(short, long, default_value = defaults::SCHEMA)]
         schema: String,
@@ -51,44 +38,190 @@ pub enum Commands {
         /// Enable interactive mode (keyboard input for details) [default: true]
         #[arg(long, default_value = "true")]
         interactive: bool,
+        /// Change source: 'polling' (table statistics), 'logical' (requires wal_level=logical)
+        /// or 'triggers' (requires 'tabletrace capture install')
+        #[arg(long, default_value = defaults::SOURCE)]
+        source: String,
+        /// Maximum rows tracked per table, in row identity order (0 = unlimited)
+        #[arg(long, default_value_t = defaults::MAX_ROWS)]
+        max_rows: usize,
+        /// Fetch only rows changed since the last cycle (by xmin) on tables with a key
+        #[arg(long)]
+        incremental: bool,
+        /// Compare md5 row hashes in PostgreSQL and fetch only changed rows (tables with a key)
+        #[arg(long)]
+        row_hash: bool,
+        /// Fetch rows of TABLE whose COLUMN (e.g. updated_at) is newer than the last cycle
+        #[arg(long, value_name = "TABLE=COLUMN")]
+        since_column: Vec<String>,
+        /// Treat TABLE as insert-only and fetch rows with COLUMN past the last seen value
+        #[arg(long, value_name = "TABLE=COLUMN")]
+        append_only: Vec<String>,
+        /// Time zone for timestamps: 'local', an IANA name (e.g. 'Asia/Tokyo') or an offset (e.g. '+09:00')
+        #[arg(long, default_value = defaults::TIMEZONE)]
+        timezone: String,
+        /// strftime format for timestamps in rows and change events (e.g. '%H:%M:%S%.3f')
+        #[arg(long, value_name = "FORMAT")]
+        time_format: Option<String>,
+        /// Watch a read-only query result as a virtual table, optionally keyed by KEY columns
+        #[arg(long, value_name = "NAME[:KEY]=SQL")]
+        query: Vec<String>,
+        /// Also watch functions, triggers, policies, indexes, constraints and grants of the schema
+        #[arg(long)]
+        catalog: bool,
+        /// Group changes into history entries by 'cycle' or by 'transaction' (in commit order when known)
+        #[arg(long, default_value = defaults::GROUP_BY)]
+        group_by: String,
+        /// Record one history entry per changed table, linked by a shared cycle number
+        #[arg(long)]
+        per_table: bool,
+    },
+    /// Manage opt-in trigger-based change capture
+    Capture {
+        #[command(subcommand)]
+        action: CaptureCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CaptureCommand {
+    /// Create the tabletrace schema and capture triggers on every table in the schema
+    Install {
+        #[command(flatten)]
+        connection: ConnectionArgs,
+        /// Schema whose tables get capture triggers (use 'all' for all schemas)
+        #[arg(short, long, default_value = defaults::SCHEMA)]
+        schema: String,
+    },
+    /// Remove all capture triggers and the tabletrace schema
+    Uninstall {
+        #[command(flatten)]
+        connection: ConnectionArgs,
     },
 }
 
+/// Database connection options
+#[derive(Args)]
+pub struct ConnectionArgs {
+    /// Preset connection: 'supabase' (local Docker), 'postgres' (local default)
+    #[arg(long)]
+    preset: Option<String>,
+    /// Database host
+    #[arg(short = 'H', long, default_value = defaults::HOST)]
+    host: String,
+    /// Database port
+    #[arg(short = 'P', long, default_value_t = defaults::PORT)]
+    port: u16,
+    /// Database name
+    #[arg(short, long)]
+    database: Option<String>,
+    /// Database user
+    #[arg(short, long, default_value = defaults::USER)]
+    user: String,
+    /// Database password (or use PGPASSWORD environment variable)
+    #[arg(short = 'W', long)]
+    password: Option<String>,
+}
+
+impl ConnectionArgs {
+    /// Generate ConnectionConfig from connection options
+    fn into_connection_config(self) -> Result<ConnectionConfig> {
+        let connection = match self.preset {
+            Some(p) => ConnectionConfig::from_preset(&p)?,
+            None => {
+                let db = self.database.ok_or(TableTraceError::DatabaseRequired)?;
+                let pass = resolve_password(self.password);
+                ConnectionConfig::new(self.host, self.port, db, self.user, pass)
+            }
+        };
+        connection.validate()?;
+        Ok(connection)
+    }
+}
+
 impl Commands {
     /// Generate WatchConfig from Watch command
     pub fn into_watch_config(self) -> Result<WatchConfig> {
         match self {
             Commands::Watch {
-                preset,
-                host,
-                port,
-                database,
-                user,
-                password,
+                connection,
                 schema,
                 interval,
                 interactive,
+                source,
+                max_rows,
+                incremental,
+                row_hash,
+                since_column,
+                append_only,
+                timezone,
+                time_format,
+                query,
+                catalog,
+                group_by,
+                per_table,
             } => {
-                let connection = match preset {
-                    Some(p) => ConnectionConfig::from_preset(&p)?,
-                    None => {
-                        let db = database.ok_or(TableTraceError::DatabaseRequired)?;
-                        let pass = resolve_password(password);
-                        ConnectionConfig::new(host, port, db, user, pass)
-                    }
-                };
-
-                let config = WatchConfig::new(connection, schema)
+                let config = WatchConfig::new(connection.into_connection_config()?, schema)
                     .with_interval(interval)
-                    .with_interactive(interactive);
+                    .with_interactive(interactive)
+                    .with_source(ChangeSource::from_name(&source)?)
+                    .with_max_rows(max_rows)
+                    .with_incremental(incremental)
+                    .with_row_hash(row_hash)
+                    .with_since_columns(parse_table_columns(since_column, "--since-column")?)
+                    .with_append_only(parse_table_columns(append_only, "--append-only")?)
+                    .with_timezone(DisplayTimeZone::from_name(&timezone)?)
+                    .with_time_format(time_format)
+                    .with_queries(
+                        query
+                            .iter()
+                            .map(|spec| QuerySpec::parse(spec))
+                            .collect::<Result<_>>()?,
+                    )
+                    .with_catalog(catalog)
+                    .with_grouping(EventGrouping::from_name(&group_by)?)
+                    .with_per_table(per_table);
 
                 config.validate()?;
                 Ok(config)
             }
+            Commands::Capture { .. } => Err(TableTraceError::config("Not a watch command")),
+        }
+    }
+}
+
+impl CaptureCommand {
+    /// Generate CaptureConfig from Capture subcommand
+    pub fn into_capture_config(self) -> Result<CaptureConfig> {
+        match self {
+            CaptureCommand::Install { connection, schema } => Ok(CaptureConfig {
+                connection: connection.into_connection_config()?,
+                action: CaptureAction::Install { schema },
+            }),
+            CaptureCommand::Uninstall { connection } => Ok(CaptureConfig {
+                connection: connection.into_connection_config()?,
+                action: CaptureAction::Uninstall,
+            }),
         }
     }
 }
 
+/// Parse `TABLE=COLUMN` option values
+fn parse_table_columns(specs: Vec<String>, option: &str) -> Result<Vec<(String, String)>> {
+    specs
+        .into_iter()
+        .map(|spec| match spec.split_once('=') {
+            Some((table, column)) if !table.is_empty() && !column.is_empty() => {
+                Ok((table.to_string(), column.to_string()))
+            }
+            _ => Err(TableTraceError::config(format!(
+                "Invalid {} '{}': expected TABLE=COLUMN",
+                option, spec
+            ))),
+        })
+        .collect()
+}
+
 /// Resolve password from argument or environment variable
 fn resolve_password(password: Option<String>) -> String {
     password
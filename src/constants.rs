@@ -11,18 +11,51 @@ pub mod display {
 
     /// Number of spaces to clear prompt line
     pub const PROMPT_CLEAR_WIDTH: usize = 60;
+
+    /// Default format of `timestamp` values
+    pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+    /// Default format of `timestamptz` values
+    pub const TIMESTAMPTZ_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f%:z";
+
+    /// Default format of change event times
+    pub const EVENT_TIME_FORMAT: &str = "%H:%M:%S";
+
+    /// Unchanged lines shown around each change in a definition diff
+    pub const DEFINITION_CONTEXT_LINES: usize = 2;
 }
 
 /// Database-related constants
 pub mod db {
-    /// Maximum number of rows to fetch per table
-    pub const MAX_ROWS_PER_TABLE: usize = 1000;
-
     /// Maximum number of debounce iterations
     pub const DEBOUNCE_MAX_ITERATIONS: usize = 5;
 
     /// Debounce wait interval (milliseconds)
     pub const DEBOUNCE_INTERVAL_MS: u64 = 100;
+
+    /// Name prefix for temporary logical replication slots
+    pub const LOGICAL_SLOT_PREFIX: &str = "tabletrace_";
+
+    /// Output plugin used for logical decoding
+    pub const LOGICAL_DECODING_PLUGIN: &str = "test_decoding";
+}
+
+/// Trigger capture constants
+pub mod capture {
+    /// Schema holding the audit table and trigger function
+    pub const SCHEMA: &str = "tabletrace";
+
+    /// Name of the per-table capture trigger
+    pub const TRIGGER_NAME: &str = "tabletrace_capture";
+
+    /// Name of the per-table TRUNCATE capture trigger
+    pub const TRUNCATE_TRIGGER_NAME: &str = "tabletrace_capture_truncate";
+
+    /// NOTIFY channel used by the capture trigger
+    pub const CHANNEL: &str = "tabletrace";
+
+    /// Age after which unread oversized changes are deleted from the audit table
+    pub const AUDIT_RETENTION: &str = "5 minutes";
 }
 
 /// Default values
@@ -41,6 +74,18 @@ pub mod defaults {
 
     /// Default schema
     pub const SCHEMA: &str = "public";
+
+    /// Default change source
+    pub const SOURCE: &str = "polling";
+
+    /// Default grouping of changes into events
+    pub const GROUP_BY: &str = "cycle";
+
+    /// Default maximum number of rows tracked per table
+    pub const MAX_ROWS: usize = 1000;
+
+    /// Default display time zone
+    pub const TIMEZONE: &str = "local";
 }
 
 /// Preset configurations
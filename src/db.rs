@@ -1,182 +1,1033 @@
 //! Database operations module
 
+use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
-use std::collections::HashMap;
-use tokio_postgres::Client;
+use std::collections::{BTreeMap, HashMap};
+use tokio_postgres::types::{FromSql, ToSql, Type};
+use tokio_postgres::{Client, Row};
 
-use crate::constants::db::MAX_ROWS_PER_TABLE;
-use crate::types::{RowData, TableStats};
+use crate::constants::capture;
+use crate::constants::db::LOGICAL_DECODING_PLUGIN;
+use crate::decoding::{json_to_row, DecodedChange};
+use crate::types::{
+    Column, ForeignKey, IdentityStrategy, RelationKind, RowData, RowIdentity, TableMeta, TableStats,
+};
+use crate::value::Value;
 
-/// Get primary key column name
-pub async fn get_primary_key(client: &Client, schema: &str, table: &str) -> Option<String> {
-    let query = r#"
-        SELECT a.attname
+/// Quote an SQL identifier
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Discover the row identity of a table
+///
+/// Uses, in order: the primary key, the `REPLICA IDENTITY USING INDEX` index,
+/// another unique index on NOT NULL columns, or all columns when the table
+/// has `REPLICA IDENTITY FULL`.
+pub async fn get_row_identity(client: &Client, schema: &str, table: &str) -> RowIdentity {
+    let index_query = r#"
+        SELECT i.indisprimary, i.indisreplident, c.relname,
+               array_agg(a.attname::text ORDER BY k.ord),
+               array_agg(format_type(a.atttypid, a.atttypmod) ORDER BY k.ord)
         FROM pg_index i
-        JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
-        WHERE i.indrelid = ($1 || '.' || $2)::regclass AND i.indisprimary
+        JOIN pg_class c ON c.oid = i.indexrelid
+        CROSS JOIN LATERAL unnest(i.indkey::int2[]) WITH ORDINALITY AS k(attnum, ord)
+        JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = k.attnum
+        WHERE i.indrelid = format('%I.%I', $1::text, $2::text)::regclass
+          AND (i.indisprimary
+               OR (i.indisunique AND i.indimmediate
+                   AND i.indpred IS NULL AND i.indexprs IS NULL))
+        GROUP BY i.indexrelid, i.indisprimary, i.indisreplident, c.relname
+        HAVING i.indisprimary OR bool_and(a.attnotnull)
+        ORDER BY i.indisprimary DESC, i.indisreplident DESC, count(*), c.relname
         LIMIT 1
     "#;
 
-    client
-        .query_opt(query, &[&schema, &table])
-        .await
-        .ok()
-        .flatten()
-        .map(|row| row.get::<_, String>(0))
+    if let Ok(Some(row)) = client.query_opt(index_query, &[&schema, &table]).await {
+        let strategy = if row.get::<_, bool>(0) {
+            IdentityStrategy::PrimaryKey
+        } else if row.get::<_, bool>(1) {
+            IdentityStrategy::ReplicaIdentity(Some(row.get(2)))
+        } else {
+            IdentityStrategy::UniqueIndex(row.get(2))
+        };
+        return RowIdentity {
+            strategy,
+            columns: row.get(3),
+            types: row.get(4),
+        };
+    }
+
+    let full_query = r#"
+        SELECT array_agg(a.attname::text ORDER BY a.attnum),
+               array_agg(format_type(a.atttypid, a.atttypmod) ORDER BY a.attnum)
+        FROM pg_class c
+        JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+        WHERE c.oid = format('%I.%I', $1::text, $2::text)::regclass
+          AND c.relreplident = 'f'
+        GROUP BY c.oid
+    "#;
+
+    if let Ok(Some(row)) = client.query_opt(full_query, &[&schema, &table]).await {
+        return RowIdentity {
+            strategy: IdentityStrategy::ReplicaIdentity(None),
+            columns: row.get(0),
+            types: row.get(1),
+        };
+    }
+
+    RowIdentity::none()
+}
+
+/// Order key expressions (with their SQL types) for a table aliased as `t`
+///
+/// Tables with a key are ordered by its columns, all others by row value.
+fn order_keys(identity: &RowIdentity) -> Vec<(String, String)> {
+    if identity.is_key() {
+        identity
+            .columns
+            .iter()
+            .zip(&identity.types)
+            .map(|(col, ty)| (format!("t.{}", quote_ident(col)), ty.clone()))
+            .collect()
+    } else {
+        vec![("ROW(t.*)::text".to_string(), "text".to_string())]
+    }
+}
+
+/// SQL expression hashing a whole row of the table aliased as `t`
+const ROW_HASH: &str = "md5(ROW(t.*)::text)";
+
+/// Additional row condition for incremental fetches
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowFilter {
+    /// Rows written by transactions at or after the given xid
+    ChangedSince(String),
+    /// Rows whose `column` is past `from` (or at it when `inclusive`) and at most `to`
+    ///
+    /// Rows written at or after the `written_since` xid are included even
+    /// when their `column` is not past `from`.
+    ColumnRange {
+        column: String,
+        column_type: String,
+        from: Option<String>,
+        inclusive: bool,
+        to: String,
+        written_since: Option<String>,
+    },
+    /// Rows whose hash (see `fetch_row_keys`) is one of the given values
+    HashIn(Vec<String>),
+}
+
+/// Build the `WHERE` clause and its parameters
+///
+/// Restricts rows to those up to `boundary` (in order key order) and to
+/// those matching `filter`.
+fn where_clause(
+    keys: &[(String, String)],
+    boundary: Option<&[String]>,
+    filter: Option<&RowFilter>,
+) -> (String, Vec<String>) {
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+
+    if let Some(boundary) = boundary {
+        let exprs: Vec<&str> = keys.iter().map(|(expr, _)| expr.as_str()).collect();
+        let placeholders: Vec<String> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, (_, ty))| format!("${}::text::{}", i + 1, ty))
+            .collect();
+        conditions.push(format!(
+            "({}) <= ({})",
+            exprs.join(", "),
+            placeholders.join(", ")
+        ));
+        params.extend(boundary.iter().cloned());
+    }
+
+    match filter {
+        Some(RowFilter::ChangedSince(xid)) => {
+            params.push(xid.clone());
+            conditions.push(format!("age(t.xmin) <= age(${}::text::xid)", params.len()));
+        }
+        Some(RowFilter::ColumnRange {
+            column,
+            column_type,
+            from,
+            inclusive,
+            to,
+            written_since,
+        }) => {
+            let column = format!("t.{}", quote_ident(column));
+            if let Some(from) = from {
+                params.push(from.clone());
+                let op = if *inclusive { ">=" } else { ">" };
+                let mut condition =
+                    format!("{} {} ${}::text::{}", column, op, params.len(), column_type);
+                // Rows committed after rows with a later column value
+                if let Some(xid) = written_since {
+                    params.push(xid.clone());
+                    condition = format!(
+                        "({} OR age(t.xmin) <= age(${}::text::xid))",
+                        condition,
+                        params.len()
+                    );
+                }
+                conditions.push(condition);
+            }
+            params.push(to.clone());
+            conditions.push(format!(
+                "{} <= ${}::text::{}",
+                column,
+                params.len(),
+                column_type
+            ));
+        }
+        Some(RowFilter::HashIn(hashes)) => {
+            // md5 digests are plain hex, so no array element quoting is needed
+            params.push(format!("{{{}}}", hashes.join(",")));
+            conditions.push(format!(
+                "{} = ANY(${}::text::text[])",
+                ROW_HASH,
+                params.len()
+            ));
+        }
+        None => {}
+    }
+
+    if conditions.is_empty() {
+        (String::new(), params)
+    } else {
+        (format!(" WHERE {}", conditions.join(" AND ")), params)
+    }
+}
+
+/// Borrow string parameters for a query
+fn as_params(params: &[String]) -> Vec<&(dyn ToSql + Sync)> {
+    params.iter().map(|v| v as &(dyn ToSql + Sync)).collect()
 }
 
-/// Fetch all rows from table (up to MAX_ROWS_PER_TABLE rows)
-pub async fn fetch_all_rows(
+/// Fetch rows in row identity order, with the order key of each row
+///
+/// Only rows up to `boundary` and matching `filter` are returned when given,
+/// and at most `limit` rows when it is non-zero.
+pub async fn fetch_rows(
     client: &Client,
     schema: &str,
     table: &str,
-) -> Result<Vec<RowData>, Box<dyn std::error::Error + Send + Sync>> {
-    let query = format!(
-        "SELECT * FROM \"{}\".\"{}\" LIMIT {}",
-        schema, table, MAX_ROWS_PER_TABLE
+    meta: &TableMeta,
+    boundary: Option<&[String]>,
+    filter: Option<&RowFilter>,
+    limit: usize,
+) -> Result<Vec<(Vec<String>, RowData)>, Box<dyn std::error::Error + Send + Sync>> {
+    let keys = order_keys(&meta.identity);
+    let exprs: Vec<&str> = keys.iter().map(|(expr, _)| expr.as_str()).collect();
+    let key_columns: Vec<String> = exprs.iter().map(|e| format!("({})::text", e)).collect();
+    let (clause, params) = where_clause(&keys, boundary, filter);
+
+    let mut query = format!(
+        "SELECT {}, {} FROM {}.{} t{} ORDER BY {}",
+        select_columns(&meta.columns),
+        key_columns.join(", "),
+        quote_ident(schema),
+        quote_ident(table),
+        clause,
+        exprs.join(", ")
     );
-    let rows = client.query(&query, &[]).await?;
+    if limit > 0 {
+        query.push_str(&format!(" LIMIT {}", limit));
+    }
+
+    let rows = client.query(&query, &as_params(&params)).await?;
 
     Ok(rows
         .iter()
         .map(|row| {
-            row.columns()
+            let data_len = row.len() - keys.len();
+            let key = (data_len..row.len()).map(|i| row.get(i)).collect();
+            let data = row.columns()[..data_len]
                 .iter()
                 .enumerate()
-                .map(|(i, col)| (col.name().to_string(), get_column_value(row, i)))
-                .collect()
+                .map(|(i, col)| match meta.columns.get(i) {
+                    Some(column) => (column.name.clone(), decode_column(row, i, column)),
+                    None => (col.name().to_string(), get_column_value(row, i)),
+                })
+                .collect();
+            (key, data)
         })
         .collect())
 }
 
-/// Convert PostgreSQL value to string
-fn get_column_value(row: &tokio_postgres::Row, idx: usize) -> String {
-    // Try each type in order
-    try_get_uuid(row, idx)
-        .or_else(|| try_get_json(row, idx))
-        .or_else(|| try_get_decimal(row, idx))
-        .or_else(|| try_get_naive_datetime(row, idx))
-        .or_else(|| try_get_datetime_utc(row, idx))
-        .or_else(|| try_get_string(row, idx))
-        .or_else(|| try_get_i64(row, idx))
-        .or_else(|| try_get_i32(row, idx))
-        .or_else(|| try_get_f64(row, idx))
-        .or_else(|| try_get_bool(row, idx))
-        .unwrap_or_else(|| "?".to_string())
-}
-
-fn try_get_uuid(row: &tokio_postgres::Row, idx: usize) -> Option<String> {
-    row.try_get::<_, Option<uuid::Uuid>>(idx).ok().map(|v| {
-        v.map(|u| u.to_string())
-            .unwrap_or_else(|| "NULL".to_string())
-    })
+/// Identity of a fetched row
+#[derive(Debug, Clone)]
+pub struct RowKey {
+    /// Order key (as text) in row identity order
+    pub order_key: Vec<String>,
+    /// Identity column values, as row key text
+    pub values: Vec<String>,
+    /// md5 of the row text, when requested
+    pub hash: Option<String>,
 }
 
-fn try_get_json(row: &tokio_postgres::Row, idx: usize) -> Option<String> {
-    row.try_get::<_, Option<serde_json::Value>>(idx)
-        .ok()
-        .map(|v| {
-            v.map(|j| j.to_string())
-                .unwrap_or_else(|| "NULL".to_string())
+/// Fetch only the identity columns of rows, in row identity order
+///
+/// With `with_hash`, an md5 of each whole row is computed server-side as
+/// well. Only meaningful for tables ordered by a key.
+pub async fn fetch_row_keys(
+    client: &Client,
+    schema: &str,
+    table: &str,
+    meta: &TableMeta,
+    boundary: Option<&[String]>,
+    limit: usize,
+    with_hash: bool,
+) -> Result<Vec<RowKey>, Box<dyn std::error::Error + Send + Sync>> {
+    let keys = order_keys(&meta.identity);
+    let exprs: Vec<&str> = keys.iter().map(|(expr, _)| expr.as_str()).collect();
+    let key_columns: Vec<Option<&Column>> = meta
+        .identity
+        .columns
+        .iter()
+        .map(|name| meta.columns.iter().find(|c| c.name == *name))
+        .collect();
+    let mut columns: Vec<String> = exprs.iter().map(|e| format!("({})::text", e)).collect();
+    columns.extend(
+        key_columns
+            .iter()
+            .zip(&exprs)
+            .map(|(column, expr)| column.map_or(expr.to_string(), column_expr)),
+    );
+    if with_hash {
+        columns.push(ROW_HASH.to_string());
+    }
+    let (clause, params) = where_clause(&keys, boundary, None);
+
+    let mut query = format!(
+        "SELECT {} FROM {}.{} t{} ORDER BY {}",
+        columns.join(", "),
+        quote_ident(schema),
+        quote_ident(table),
+        clause,
+        exprs.join(", ")
+    );
+    if limit > 0 {
+        query.push_str(&format!(" LIMIT {}", limit));
+    }
+
+    let rows = client.query(&query, &as_params(&params)).await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let n = keys.len();
+            RowKey {
+                order_key: (0..n).map(|i| row.get(i)).collect(),
+                // Read like fetched rows, so the values match `get_pk_value`
+                values: key_columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, column)| match column {
+                        Some(column) => decode_column(row, n + i, column).to_string(),
+                        None => get_column_value(row, n + i).to_string(),
+                    })
+                    .collect(),
+                hash: with_hash.then(|| row.get(2 * n)),
+            }
         })
+        .collect())
 }
 
-fn try_get_decimal(row: &tokio_postgres::Row, idx: usize) -> Option<String> {
-    row.try_get::<_, Option<Decimal>>(idx).ok().map(|v| {
-        v.map(|d| d.to_string())
-            .unwrap_or_else(|| "NULL".to_string())
-    })
+/// Check whether a table has rows written before transaction `xid`
+pub async fn has_rows_before(
+    client: &Client,
+    schema: &str,
+    table: &str,
+    xid: &str,
+) -> Result<bool, tokio_postgres::Error> {
+    let query = format!(
+        "SELECT EXISTS (SELECT 1 FROM {}.{} t WHERE age(t.xmin) > age($1::text::xid))",
+        quote_ident(schema),
+        quote_ident(table)
+    );
+    let row = client.query_one(&query, &[&xid]).await?;
+    Ok(row.get(0))
 }
 
-fn try_get_naive_datetime(row: &tokio_postgres::Row, idx: usize) -> Option<String> {
-    row.try_get::<_, Option<chrono::NaiveDateTime>>(idx)
-        .ok()
-        .map(|v| {
-            v.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
-                .unwrap_or_else(|| "NULL".to_string())
+/// 64-bit id of the transaction in the `xmin` of a row of the table aliased as `t`
+///
+/// `xmin` holds only the low 32 bits; ids above those of the next
+/// transaction id belong to the previous epoch.
+const FULL_XMIN: &str = "(txid_snapshot_xmax(txid_current_snapshot()) / 4294967296 \
+     - (t.xmin::text::bigint > txid_snapshot_xmax(txid_current_snapshot()) % 4294967296)::int) \
+     * 4294967296 + t.xmin::text::bigint";
+
+/// Get the transaction that last wrote each of the given rows (by identity values)
+///
+/// Returns the `xmin` of each row found (with its epoch, so that ids keep
+/// their order across wraparound), with its commit time when
+/// `track_commit_timestamp` is on.
+pub async fn fetch_row_transactions(
+    client: &Client,
+    schema: &str,
+    table: &str,
+    meta: &TableMeta,
+    keys: &[Vec<String>],
+) -> Result<
+    HashMap<Vec<String>, (i64, Option<DateTime<Utc>>)>,
+    Box<dyn std::error::Error + Send + Sync>,
+> {
+    let identity = &meta.identity;
+    let key_columns: Vec<&Column> = identity
+        .columns
+        .iter()
+        .filter_map(|name| meta.columns.iter().find(|c| c.name == *name))
+        .collect();
+    if !identity.is_key() || keys.is_empty() || key_columns.len() != identity.columns.len() {
+        return Ok(HashMap::new());
+    }
+
+    let exprs: Vec<String> = identity
+        .columns
+        .iter()
+        .map(|col| format!("t.{}", quote_ident(col)))
+        .collect();
+    let unnested: Vec<String> = identity
+        .types
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| format!("u.c{}::{}", i, ty))
+        .collect();
+    let params: Vec<String> = (0..exprs.len())
+        .map(|i| format!("${}::text[]", i + 1))
+        .collect();
+    let aliases: Vec<String> = (0..exprs.len()).map(|i| format!("c{}", i)).collect();
+    let query = format!(
+        "SELECT {}, {}, \
+                CASE WHEN current_setting('track_commit_timestamp')::boolean \
+                     THEN pg_xact_commit_timestamp(t.xmin) END \
+         FROM {}.{} t \
+         WHERE ({}) IN (SELECT {} FROM unnest({}) AS u({}))",
+        key_columns
+            .iter()
+            .map(|c| column_expr(c))
+            .collect::<Vec<_>>()
+            .join(", "),
+        FULL_XMIN,
+        quote_ident(schema),
+        quote_ident(table),
+        exprs.join(", "),
+        unnested.join(", "),
+        params.join(", "),
+        aliases.join(", ")
+    );
+
+    // One text array per identity column
+    let columns: Vec<Vec<String>> = (0..exprs.len())
+        .map(|i| keys.iter().map(|key| key[i].clone()).collect())
+        .collect();
+    let params: Vec<&(dyn ToSql + Sync)> =
+        columns.iter().map(|c| c as &(dyn ToSql + Sync)).collect();
+    let rows = client.query(&query, &params).await?;
+
+    let n = key_columns.len();
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let key = key_columns
+                .iter()
+                .enumerate()
+                .map(|(i, column)| decode_column(row, i, column).to_string())
+                .collect();
+            (key, (row.get(n), row.get(n + 1)))
         })
+        .collect())
 }
 
-fn try_get_datetime_utc(row: &tokio_postgres::Row, idx: usize) -> Option<String> {
-    row.try_get::<_, Option<chrono::DateTime<chrono::Utc>>>(idx)
-        .ok()
-        .map(|v| {
-            v.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
-                .unwrap_or_else(|| "NULL".to_string())
+/// Get the SQL type of a column (`None` if the column does not exist)
+pub async fn get_column_type(
+    client: &Client,
+    schema: &str,
+    table: &str,
+    column: &str,
+) -> Result<Option<String>, tokio_postgres::Error> {
+    let row = client
+        .query_opt(
+            "SELECT format_type(a.atttypid, a.atttypmod) FROM pg_attribute a \
+             WHERE a.attrelid = format('%I.%I', $1::text, $2::text)::regclass \
+               AND a.attname = $3 AND a.attnum > 0 AND NOT a.attisdropped",
+            &[&schema, &table, &column],
+        )
+        .await?;
+    Ok(row.map(|r| r.get(0)))
+}
+
+/// Get the columns of a table in definition order
+///
+/// Domains are described by their base type.
+pub async fn get_columns(
+    client: &Client,
+    schema: &str,
+    table: &str,
+) -> Result<Vec<Column>, tokio_postgres::Error> {
+    let rows = client
+        .query(
+            "SELECT a.attnum, a.attname::text, a.atttypid, \
+                    CASE WHEN ty.typtype = 'd' \
+                         THEN format_type(ty.typbasetype, ty.typtypmod) \
+                         ELSE format_type(a.atttypid, a.atttypmod) END \
+             FROM pg_attribute a JOIN pg_type ty ON ty.oid = a.atttypid \
+             WHERE a.attrelid = format('%I.%I', $1::text, $2::text)::regclass \
+               AND a.attnum > 0 AND NOT a.attisdropped \
+             ORDER BY a.attnum",
+            &[&schema, &table],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| Column {
+            attnum: row.get(0),
+            name: row.get(1),
+            type_oid: row.get(2),
+            type_name: row.get(3),
         })
+        .collect())
 }
 
-fn try_get_string(row: &tokio_postgres::Row, idx: usize) -> Option<String> {
-    row.try_get::<_, Option<String>>(idx)
-        .ok()
-        .map(|v| v.unwrap_or_else(|| "NULL".to_string()))
+/// Get the result columns of a query
+pub async fn describe_query(
+    client: &Client,
+    sql: &str,
+) -> Result<Vec<Column>, tokio_postgres::Error> {
+    let statement = client
+        .prepare(&format!("SELECT * FROM ({}) t", sql))
+        .await?;
+
+    let mut columns = Vec::new();
+    for (position, column) in (1..).zip(statement.columns()) {
+        let type_oid = column.type_().oid();
+        let row = client
+            .query_one(
+                "SELECT CASE WHEN typtype = 'd' THEN format_type(typbasetype, typtypmod) \
+                             ELSE format_type(oid, NULL) END \
+                 FROM pg_type WHERE oid = $1",
+                &[&type_oid],
+            )
+            .await?;
+        columns.push(Column {
+            attnum: position,
+            name: column.name().to_string(),
+            type_oid,
+            type_name: row.get(0),
+        });
+    }
+    Ok(columns)
 }
 
-fn try_get_i64(row: &tokio_postgres::Row, idx: usize) -> Option<String> {
-    row.try_get::<_, Option<i64>>(idx).ok().map(|v| {
-        v.map(|n| n.to_string())
-            .unwrap_or_else(|| "NULL".to_string())
-    })
+/// Run a query in a read-only transaction and fetch its rows
+pub async fn fetch_query_rows(
+    client: &Client,
+    sql: &str,
+    columns: &[Column],
+) -> Result<Vec<RowData>, tokio_postgres::Error> {
+    let query = format!("SELECT {} FROM ({}) t", select_columns(columns), sql);
+
+    client.batch_execute("BEGIN READ ONLY").await?;
+    let result = client.query(&query, &[]).await;
+    let end = if result.is_ok() { "COMMIT" } else { "ROLLBACK" };
+    client.batch_execute(end).await?;
+
+    Ok(result?
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .enumerate()
+                .map(|(i, column)| (column.name.clone(), decode_column(row, i, column)))
+                .collect()
+        })
+        .collect())
 }
 
-fn try_get_i32(row: &tokio_postgres::Row, idx: usize) -> Option<String> {
-    row.try_get::<_, Option<i32>>(idx).ok().map(|v| {
-        v.map(|n| n.to_string())
-            .unwrap_or_else(|| "NULL".to_string())
-    })
+/// Get a hash of the column names and types of each table, keyed by `schema.table`
+///
+/// Tables that no longer exist are left out.
+pub async fn get_column_fingerprints(
+    client: &Client,
+    tables: &[(String, String)],
+) -> Result<HashMap<String, String>, tokio_postgres::Error> {
+    let relations: Vec<String> = tables
+        .iter()
+        .map(|(schema, table)| format!("{}.{}", quote_ident(schema), quote_ident(table)))
+        .collect();
+    let rows = client
+        .query(
+            "SELECT n.nspname || '.' || c.relname, \
+                    md5(string_agg(a.attnum || ':' || a.attname || ':' \
+                                   || format_type(a.atttypid, a.atttypmod), ',' ORDER BY a.attnum)) \
+             FROM pg_class c \
+             JOIN pg_namespace n ON n.oid = c.relnamespace \
+             JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped \
+             WHERE c.oid IN (SELECT to_regclass(unnest($1::text[]))) \
+             GROUP BY n.nspname, c.relname",
+            &[&relations],
+        )
+        .await?;
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
 }
 
-fn try_get_f64(row: &tokio_postgres::Row, idx: usize) -> Option<String> {
-    row.try_get::<_, Option<f64>>(idx).ok().map(|v| {
-        v.map(|n| format!("{:.2}", n))
-            .unwrap_or_else(|| "NULL".to_string())
-    })
+/// Get the largest value of a column as text (`None` for empty tables)
+pub async fn get_column_max(
+    client: &Client,
+    schema: &str,
+    table: &str,
+    column: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let query = format!(
+        "SELECT max(t.{})::text FROM {}.{} t",
+        quote_ident(column),
+        quote_ident(schema),
+        quote_ident(table)
+    );
+    let row = client.query_one(&query, &[]).await?;
+    Ok(row.get(0))
+}
+
+/// Get the oldest transaction id still running (32-bit xid as text)
+///
+/// Rows written by any transaction that commits after this call have an
+/// `xmin` at or after the returned id.
+pub async fn get_xmin_watermark(
+    client: &Client,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let row = client
+        .query_one(
+            "SELECT (txid_snapshot_xmin(txid_current_snapshot()) % 4294967296)::text",
+            &[],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+/// Types read in binary form; all others are read as `::text` and parsed
+const NATIVE_TYPES: &[Type] = &[
+    Type::BOOL,
+    Type::INT2,
+    Type::INT4,
+    Type::INT8,
+    Type::FLOAT4,
+    Type::FLOAT8,
+    Type::TEXT,
+    Type::VARCHAR,
+    Type::BPCHAR,
+    Type::NAME,
+    Type::JSON,
+    Type::JSONB,
+    Type::UUID,
+    Type::BYTEA,
+    Type::BOOL_ARRAY,
+    Type::INT2_ARRAY,
+    Type::INT4_ARRAY,
+    Type::INT8_ARRAY,
+    Type::FLOAT8_ARRAY,
+    Type::TEXT_ARRAY,
+    Type::VARCHAR_ARRAY,
+    Type::UUID_ARRAY,
+];
+
+/// Whether a column is read in binary form
+fn is_native(column: &Column) -> bool {
+    Type::from_oid(column.type_oid).is_some_and(|ty| NATIVE_TYPES.contains(&ty))
+}
+
+/// Select list for a table aliased as `t` (`t.*` when columns are unknown)
+fn select_columns(columns: &[Column]) -> String {
+    if columns.is_empty() {
+        return "t.*".to_string();
+    }
+    columns
+        .iter()
+        .map(column_expr)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Expression reading a column of the table aliased as `t`
+fn column_expr(column: &Column) -> String {
+    let expr = format!("t.{}", quote_ident(&column.name));
+    if is_native(column) {
+        expr
+    } else if Type::from_oid(column.type_oid) == Some(Type::INET) {
+        // Same as the output function; a cast to text always adds the netmask
+        format!("abbrev({})", expr)
+    } else {
+        format!("{}::text", expr)
+    }
+}
+
+/// Read a column selected by `select_columns`
+fn decode_column(row: &Row, idx: usize, column: &Column) -> Value {
+    if is_native(column) {
+        return get_column_value(row, idx);
+    }
+    match row.try_get::<_, Option<String>>(idx) {
+        Ok(Some(text)) => Value::from_text(&column.type_name, &text),
+        Ok(None) => Value::Null,
+        Err(_) => Value::Unknown,
+    }
 }
 
-fn try_get_bool(row: &tokio_postgres::Row, idx: usize) -> Option<String> {
-    row.try_get::<_, Option<bool>>(idx).ok().map(|v| {
-        v.map(|b| b.to_string())
-            .unwrap_or_else(|| "NULL".to_string())
+/// Convert PostgreSQL value to a typed value
+fn get_column_value(row: &Row, idx: usize) -> Value {
+    // Try each type in order
+    try_get(row, idx, Value::Uuid)
+        .or_else(|| try_get(row, idx, Value::Json))
+        .or_else(|| try_get(row, idx, |d: Decimal| Value::Numeric(d.to_string())))
+        .or_else(|| try_get(row, idx, Value::Timestamp))
+        .or_else(|| try_get(row, idx, Value::TimestampTz))
+        .or_else(|| try_get(row, idx, Value::Date))
+        .or_else(|| try_get(row, idx, Value::Time))
+        .or_else(|| try_get(row, idx, Value::Text))
+        .or_else(|| try_get(row, idx, Value::Int))
+        .or_else(|| try_get(row, idx, |n: i32| Value::Int(n.into())))
+        .or_else(|| try_get(row, idx, |n: i16| Value::Int(n.into())))
+        .or_else(|| try_get(row, idx, Value::Float))
+        // Through text, so that e.g. 0.1 does not widen to 0.10000000149011612
+        .or_else(|| {
+            try_get(row, idx, |n: f32| {
+                Value::Float(n.to_string().parse().unwrap_or(n.into()))
+            })
+        })
+        .or_else(|| try_get(row, idx, Value::Bool))
+        .or_else(|| try_get(row, idx, Value::Bytes))
+        .or_else(|| try_get_array(row, idx, Value::Text))
+        .or_else(|| try_get_array(row, idx, Value::Int))
+        .or_else(|| try_get_array(row, idx, |n: i32| Value::Int(n.into())))
+        .or_else(|| try_get_array(row, idx, |n: i16| Value::Int(n.into())))
+        .or_else(|| try_get_array(row, idx, Value::Float))
+        .or_else(|| try_get_array(row, idx, Value::Bool))
+        .or_else(|| try_get_array(row, idx, Value::Uuid))
+        .or_else(|| try_get_array(row, idx, |d: Decimal| Value::Numeric(d.to_string())))
+        .unwrap_or(Value::Unknown)
+}
+
+/// Get a nullable column as `T`, if the column type matches
+fn try_get<'a, T: FromSql<'a>>(
+    row: &'a Row,
+    idx: usize,
+    wrap: impl FnOnce(T) -> Value,
+) -> Option<Value> {
+    row.try_get::<_, Option<T>>(idx)
+        .ok()
+        .map(|v| v.map_or(Value::Null, wrap))
+}
+
+/// Get a nullable array column of `T`, if the column type matches
+fn try_get_array<'a, T: FromSql<'a>>(
+    row: &'a Row,
+    idx: usize,
+    wrap: impl Fn(T) -> Value,
+) -> Option<Value> {
+    try_get(row, idx, |items: Vec<Option<T>>| {
+        Value::Array(
+            items
+                .into_iter()
+                .map(|item| item.map_or(Value::Null, &wrap))
+                .collect(),
+        )
     })
 }
 
-/// Get all available tables
+/// Get all watchable relations
+///
+/// Tables, partitioned tables, views, materialized views and foreign tables.
+/// Partitions are left out: changes to them are shown on their partitioned table.
 pub async fn get_all_tables(
     client: &Client,
     schema_filter: &str,
 ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
-    let rows = if schema_filter.to_lowercase() == "all" {
-        client
-            .query(
-                "SELECT schemaname, relname FROM pg_stat_user_tables ORDER BY schemaname, relname",
-                &[],
-            )
-            .await?
+    let condition = "c.relkind IN ('r', 'p', 'v', 'm', 'f') \
+         AND NOT EXISTS (SELECT 1 FROM pg_inherits i JOIN pg_class pc ON pc.oid = i.inhparent \
+                         WHERE i.inhrelid = c.oid AND pc.relkind = 'p')";
+    list_relations(client, schema_filter, condition).await
+}
+
+/// Get all plain tables, including partitions (the relations that can carry row triggers)
+pub async fn get_base_tables(
+    client: &Client,
+    schema_filter: &str,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    list_relations(client, schema_filter, "c.relkind = 'r'").await
+}
+
+/// List user relations matching a `pg_class` condition
+async fn list_relations(
+    client: &Client,
+    schema_filter: &str,
+    condition: &str,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let query = format!(
+        "SELECT n.nspname, c.relname FROM pg_class c \
+         JOIN pg_namespace n ON n.oid = c.relnamespace \
+         WHERE {} \
+           AND n.nspname NOT IN ('pg_catalog', 'information_schema') \
+           AND n.nspname !~ '^pg_(toast|temp)' \
+           AND ($1 = 'all' OR n.nspname = $1) \
+         ORDER BY n.nspname, c.relname",
+        condition
+    );
+    let rows = client
+        .query(&query, &[&schema_filter_param(schema_filter)])
+        .await?;
+
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// Normalize a `--schema` value for the `$1 = 'all' OR nspname = $1` filter
+fn schema_filter_param(schema_filter: &str) -> String {
+    if schema_filter.eq_ignore_ascii_case("all") {
+        "all".to_string()
     } else {
-        client
-            .query(
-                "SELECT schemaname, relname FROM pg_stat_user_tables WHERE schemaname = $1 ORDER BY schemaname, relname",
-                &[&schema_filter],
-            )
-            .await?
+        schema_filter.to_string()
+    }
+}
+
+/// Query listing catalog objects of the filtered schemas as `(object, definition)`
+///
+/// Covers functions and procedures (not those of extensions), triggers,
+/// row level security and its policies, indexes, table constraints and the
+/// privileges granted on relations, functions and schemas.
+fn catalog_objects_query() -> String {
+    let filter = "n.nspname NOT IN ('pg_catalog', 'information_schema') \
+                  AND n.nspname !~ '^pg_(toast|temp)' \
+                  AND ($1 = 'all' OR n.nspname = $1)";
+    let acl = |column: &str| {
+        format!(
+            "array_to_string(ARRAY(SELECT a::text FROM unnest({}) a ORDER BY 1), E'\\n')",
+            column
+        )
     };
+    format!(
+        "SELECT 'function ' || n.nspname || '.' || p.proname \
+                || '(' || pg_get_function_identity_arguments(p.oid) || ')', \
+                pg_get_functiondef(p.oid) \
+         FROM pg_proc p JOIN pg_namespace n ON n.oid = p.pronamespace \
+         WHERE p.prokind IN ('f', 'p') AND {filter} \
+           AND NOT EXISTS (SELECT 1 FROM pg_depend d \
+                           WHERE d.classid = 'pg_proc'::regclass AND d.objid = p.oid \
+                             AND d.deptype = 'e') \
+         UNION ALL \
+         SELECT 'trigger ' || t.tgname || ' on ' || n.nspname || '.' || c.relname, \
+                pg_get_triggerdef(t.oid, true) \
+                || CASE t.tgenabled WHEN 'D' THEN ' -- disabled' ELSE '' END \
+         FROM pg_trigger t \
+         JOIN pg_class c ON c.oid = t.tgrelid \
+         JOIN pg_namespace n ON n.oid = c.relnamespace \
+         WHERE NOT t.tgisinternal AND {filter} \
+         UNION ALL \
+         SELECT 'row level security on ' || n.nspname || '.' || c.relname, \
+                CASE WHEN c.relforcerowsecurity THEN 'enabled, forced' ELSE 'enabled' END \
+         FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+         WHERE c.relrowsecurity AND {filter} \
+         UNION ALL \
+         SELECT 'policy ' || p.policyname || ' on ' || n.nspname || '.' || p.tablename, \
+                p.permissive || ' FOR ' || p.cmd || ' TO ' || array_to_string(p.roles, ', ') \
+                || COALESCE(E'\\nUSING (' || p.qual || ')', '') \
+                || COALESCE(E'\\nWITH CHECK (' || p.with_check || ')', '') \
+         FROM pg_policies p JOIN pg_namespace n ON n.nspname = p.schemaname \
+         WHERE {filter} \
+         UNION ALL \
+         SELECT 'index ' || n.nspname || '.' || c.relname, pg_get_indexdef(i.indexrelid) \
+         FROM pg_index i \
+         JOIN pg_class c ON c.oid = i.indexrelid \
+         JOIN pg_namespace n ON n.oid = c.relnamespace \
+         WHERE {filter} \
+         UNION ALL \
+         SELECT 'constraint ' || co.conname || ' on ' || n.nspname || '.' || c.relname, \
+                pg_get_constraintdef(co.oid, true) \
+         FROM pg_constraint co \
+         JOIN pg_class c ON c.oid = co.conrelid \
+         JOIN pg_namespace n ON n.oid = c.relnamespace \
+         WHERE {filter} \
+         UNION ALL \
+         SELECT 'grants on ' || n.nspname || '.' || c.relname, {relacl} \
+         FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+         WHERE c.relacl IS NOT NULL AND c.relkind IN ('r', 'p', 'v', 'm', 'f', 'S') \
+           AND {filter} \
+         UNION ALL \
+         SELECT 'grants on function ' || n.nspname || '.' || p.proname \
+                || '(' || pg_get_function_identity_arguments(p.oid) || ')', {proacl} \
+         FROM pg_proc p JOIN pg_namespace n ON n.oid = p.pronamespace \
+         WHERE p.proacl IS NOT NULL AND {filter} \
+         UNION ALL \
+         SELECT 'grants on schema ' || n.nspname, {nspacl} \
+         FROM pg_namespace n \
+         WHERE n.nspacl IS NOT NULL AND {filter}",
+        filter = filter,
+        relacl = acl("c.relacl"),
+        proacl = acl("p.proacl"),
+        nspacl = acl("n.nspacl"),
+    )
+}
 
+/// Get the definitions of the catalog objects in a schema, keyed by object
+pub async fn get_catalog_objects(
+    client: &Client,
+    schema_filter: &str,
+) -> Result<BTreeMap<String, String>, tokio_postgres::Error> {
+    let rows = client
+        .query(
+            &catalog_objects_query(),
+            &[&schema_filter_param(schema_filter)],
+        )
+        .await?;
     Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
 }
 
+/// Get a hash of the definitions of all catalog objects in a schema
+pub async fn get_catalog_hash(
+    client: &Client,
+    schema_filter: &str,
+) -> Result<String, tokio_postgres::Error> {
+    let query = format!(
+        "SELECT md5(COALESCE(string_agg(o.object || E'\\n' || o.definition, E'\\n\\n' \
+                                        ORDER BY o.object), '')) \
+         FROM ({}) o(object, definition)",
+        catalog_objects_query()
+    );
+    let row = client
+        .query_one(&query, &[&schema_filter_param(schema_filter)])
+        .await?;
+    Ok(row.get(0))
+}
+
+/// Get the kind of a relation
+pub async fn get_relation_kind(
+    client: &Client,
+    schema: &str,
+    table: &str,
+) -> Result<RelationKind, tokio_postgres::Error> {
+    let relation = format!("{}.{}", quote_ident(schema), quote_ident(table));
+    let row = client
+        .query_one(
+            "SELECT relkind::text FROM pg_class WHERE oid = $1::text::regclass",
+            &[&relation],
+        )
+        .await?;
+    Ok(RelationKind::from_relkind(row.get(0)))
+}
+
+/// Get the leaf partitions of a partitioned table as `schema.table` keys
+pub async fn get_partitions(
+    client: &Client,
+    schema: &str,
+    table: &str,
+) -> Result<Vec<String>, tokio_postgres::Error> {
+    let relation = format!("{}.{}", quote_ident(schema), quote_ident(table));
+    let rows = client
+        .query(
+            "WITH RECURSIVE tree AS ( \
+                 SELECT inhrelid AS oid FROM pg_inherits WHERE inhparent = $1::text::regclass \
+                 UNION ALL \
+                 SELECT i.inhrelid FROM pg_inherits i JOIN tree ON i.inhparent = tree.oid \
+             ) \
+             SELECT n.nspname || '.' || c.relname FROM tree \
+             JOIN pg_class c ON c.oid = tree.oid \
+             JOIN pg_namespace n ON n.oid = c.relnamespace \
+             WHERE c.relkind = 'r'",
+            &[&relation],
+        )
+        .await?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Get the foreign keys of a table, in constraint name order
+pub async fn get_foreign_keys(
+    client: &Client,
+    schema: &str,
+    table: &str,
+) -> Result<Vec<ForeignKey>, tokio_postgres::Error> {
+    let relation = format!("{}.{}", quote_ident(schema), quote_ident(table));
+    let rows = client
+        .query(
+            "SELECT ARRAY(SELECT a.attname::text FROM unnest(c.conkey) WITH ORDINALITY AS k(attnum, ord) \
+                          JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = k.attnum \
+                          ORDER BY k.ord), \
+                    n.nspname || '.' || r.relname, \
+                    ARRAY(SELECT a.attname::text FROM unnest(c.confkey) WITH ORDINALITY AS k(attnum, ord) \
+                          JOIN pg_attribute a ON a.attrelid = c.confrelid AND a.attnum = k.attnum \
+                          ORDER BY k.ord), \
+                    c.confdeltype = 'c' \
+             FROM pg_constraint c \
+             JOIN pg_class r ON r.oid = c.confrelid \
+             JOIN pg_namespace n ON n.oid = r.relnamespace \
+             WHERE c.contype = 'f' AND c.conrelid = $1::text::regclass AND c.conparentid = 0 \
+             ORDER BY c.conname",
+            &[&relation],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| ForeignKey {
+            columns: row.get(0),
+            referenced_table: row.get(1),
+            referenced_columns: row.get(2),
+            on_delete_cascade: row.get(3),
+        })
+        .collect())
+}
+
+/// Hash the whole content of a relation, independent of row order
+pub async fn get_content_hash(
+    client: &Client,
+    schema: &str,
+    table: &str,
+) -> Result<String, tokio_postgres::Error> {
+    let query = format!(
+        "SELECT md5(COALESCE(string_agg(h, ',' ORDER BY h), '')) \
+         FROM (SELECT {} AS h FROM {}.{} t) rows",
+        ROW_HASH,
+        quote_ident(schema),
+        quote_ident(table)
+    );
+    let row = client.query_one(&query, &[]).await?;
+    Ok(row.get(0))
+}
+
 /// Get table statistics
+///
+/// Counters of a partitioned table are summed over its partitions; relations
+/// without counters (views, materialized views, foreign tables) report zero.
 pub async fn get_table_stats(
     client: &Client,
     tables: &[(String, String)],
 ) -> Result<HashMap<(String, String), TableStats>, Box<dyn std::error::Error>> {
     let mut stats = HashMap::new();
 
+    // Statistics may be cached until the end of the transaction
+    // (stats_fetch_consistency), so always read fresh ones
+    client
+        .execute("SELECT pg_stat_clear_snapshot()", &[])
+        .await?;
+
     for (schema, table) in tables {
+        let relation = format!("{}.{}", quote_ident(schema), quote_ident(table));
         let row = client
             .query_one(
-                "SELECT COALESCE(n_tup_ins, 0), COALESCE(n_tup_upd, 0), COALESCE(n_tup_del, 0) \
-                 FROM pg_stat_user_tables WHERE schemaname = $1 AND relname = $2",
-                &[schema, table],
+                "WITH RECURSIVE tree AS ( \
+                     SELECT $1::text::regclass::oid AS oid \
+                     UNION ALL \
+                     SELECT i.inhrelid FROM pg_inherits i JOIN tree ON i.inhparent = tree.oid \
+                 ) \
+                 SELECT COALESCE(SUM(s.n_tup_ins), 0)::bigint, COALESCE(SUM(s.n_tup_upd), 0)::bigint, \
+                        COALESCE(SUM(s.n_tup_del), 0)::bigint, COALESCE(SUM(s.n_live_tup), 0)::bigint, \
+                        string_agg(pg_relation_filenode(tree.oid)::text, ',' ORDER BY tree.oid), \
+                        (txid_snapshot_xmin(txid_current_snapshot()) % 4294967296)::text, \
+                        (SELECT COALESCE(d.stats_reset::text, '') FROM pg_stat_database d \
+                         WHERE d.datname = current_database()) \
+                        || '/' || pg_postmaster_start_time()::text \
+                 FROM tree LEFT JOIN pg_stat_user_tables s ON s.relid = tree.oid",
+                &[&relation],
             )
             .await?;
 
@@ -186,6 +1037,10 @@ pub async fn get_table_stats(
                 n_tup_ins: row.get(0),
                 n_tup_upd: row.get(1),
                 n_tup_del: row.get(2),
+                n_live_tup: row.get(3),
+                filenodes: row.get(4),
+                horizon: row.get(5),
+                epoch: row.get(6),
             },
         );
     }
@@ -193,19 +1048,117 @@ pub async fn get_table_stats(
     Ok(stats)
 }
 
-/// Detect changes in statistics
-pub fn has_stats_changes(
-    current: &HashMap<(String, String), TableStats>,
-    previous: &HashMap<(String, String), TableStats>,
-) -> bool {
-    current.iter().any(|((schema, table), stats)| {
-        let key = (schema.clone(), table.clone());
-        if let Some(prev) = previous.get(&key) {
-            stats.n_tup_ins > prev.n_tup_ins
-                || stats.n_tup_upd > prev.n_tup_upd
-                || stats.n_tup_del > prev.n_tup_del
-        } else {
-            false
-        }
-    })
+/// Get the server's `wal_level` setting
+pub async fn get_wal_level(client: &Client) -> Result<String, tokio_postgres::Error> {
+    let row = client
+        .query_one("SELECT current_setting('wal_level')", &[])
+        .await?;
+    Ok(row.get(0))
+}
+
+/// Create a temporary logical replication slot (dropped when the session ends)
+pub async fn create_logical_slot(
+    client: &Client,
+    slot_name: &str,
+) -> Result<(), tokio_postgres::Error> {
+    client
+        .execute(
+            "SELECT pg_create_logical_replication_slot($1, $2, true)",
+            &[&slot_name, &LOGICAL_DECODING_PLUGIN],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Consume pending changes from a logical replication slot
+pub async fn get_slot_changes(
+    client: &Client,
+    slot_name: &str,
+) -> Result<Vec<String>, tokio_postgres::Error> {
+    let rows = client
+        .query(
+            "SELECT data FROM pg_logical_slot_get_changes($1, NULL, NULL, \
+             'skip-empty-xacts', '1', 'include-xids', '1', 'include-timestamp', '1')",
+            &[&slot_name],
+        )
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Check whether the capture schema has been installed
+pub async fn is_capture_installed(client: &Client) -> Result<bool, tokio_postgres::Error> {
+    let table = format!("{}.audit_log", capture::SCHEMA);
+    let row = client
+        .query_one("SELECT to_regclass($1) IS NOT NULL", &[&table])
+        .await?;
+    Ok(row.get(0))
+}
+
+/// Delete captured changes nobody read (notified while no watcher was listening)
+pub async fn prune_audit_log(client: &Client) -> Result<u64, tokio_postgres::Error> {
+    let query = format!(
+        "DELETE FROM {}.audit_log WHERE captured_at < now() - $1::text::interval",
+        capture::SCHEMA
+    );
+    client.execute(&query, &[&capture::AUDIT_RETENTION]).await
+}
+
+/// Subscribe to capture trigger notifications
+pub async fn listen_capture(client: &Client) -> Result<(), tokio_postgres::Error> {
+    client
+        .batch_execute(&format!("LISTEN {}", capture::CHANNEL))
+        .await
+}
+
+/// Take a captured change from the audit table, deleting it
+pub async fn get_audit_change(
+    client: &Client,
+    id: i64,
+) -> Result<Option<DecodedChange>, tokio_postgres::Error> {
+    let query = format!(
+        "DELETE FROM {}.audit_log WHERE id = $1 \
+         RETURNING schema_name, table_name, operation, old_row, new_row, txid",
+        capture::SCHEMA
+    );
+
+    Ok(client
+        .query_opt(&query, &[&id])
+        .await?
+        .map(|row| DecodedChange {
+            schema: row.get(0),
+            table: row.get(1),
+            operation: row.get(2),
+            old_values: row
+                .get::<_, Option<serde_json::Value>>(3)
+                .and_then(|v| json_to_row(&v)),
+            new_values: row
+                .get::<_, Option<serde_json::Value>>(4)
+                .and_then(|v| json_to_row(&v)),
+            xid: row.get(5),
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_range_overlap() {
+        let filter = RowFilter::ColumnRange {
+            column: "id".to_string(),
+            column_type: "bigint".to_string(),
+            from: Some("10".to_string()),
+            inclusive: false,
+            to: "20".to_string(),
+            written_since: Some("700".to_string()),
+        };
+        let (clause, params) = where_clause(&[], None, Some(&filter));
+        assert_eq!(
+            clause,
+            " WHERE (t.\"id\" > $1::text::bigint OR age(t.xmin) <= age($2::text::xid)) \
+             AND t.\"id\" <= $3::text::bigint"
+        );
+        assert_eq!(params, vec!["10", "700", "20"]);
+    }
 }
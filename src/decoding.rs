@@ -0,0 +1,386 @@
+//! Row change decoding module
+//!
+//! Parses the text emitted by the `test_decoding` output plugin, e.g.
+//!
+//! ```text
+//! table public.users: UPDATE: old-key: id[integer]:1 new-tuple: id[integer]:2 name[text]:'Bob'
+//! ```
+//!
+//! and the JSON payloads sent by the capture triggers.
+
+use chrono::{DateTime, Utc};
+use serde_json::Value as Json;
+
+use crate::types::RowData;
+use crate::value::Value;
+
+/// Value emitted for TOASTed columns that were not modified
+const UNCHANGED_TOAST: &str = "unchanged-toast-datum";
+
+/// Single row change decoded from the WAL stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedChange {
+    pub schema: String,
+    pub table: String,
+    pub operation: String,
+    pub old_values: Option<RowData>,
+    pub new_values: Option<RowData>,
+    /// Transaction the change was made in, when known
+    pub xid: Option<i64>,
+}
+
+/// Notification payload sent by the capture trigger
+#[derive(Debug, Clone, PartialEq)]
+pub enum CapturePayload {
+    /// Complete change carried inline
+    Change(Box<DecodedChange>),
+    /// Change too large for NOTIFY; read it from the audit table
+    AuditId(i64),
+}
+
+/// Tuple section currently being parsed
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Old,
+    New,
+}
+
+/// Parse a single `test_decoding` line
+///
+/// Returns `None` for transaction markers (`BEGIN`/`COMMIT`), TRUNCATE and
+/// anything else that is not a row change.
+pub fn parse_change(line: &str) -> Option<DecodedChange> {
+    let rest = line.strip_prefix("table ")?;
+    let (schema, rest) = parse_identifier(rest)?;
+    let rest = rest.strip_prefix('.')?;
+    let (table, rest) = parse_identifier(rest)?;
+    let rest = rest.strip_prefix(": ")?;
+    let (operation, rest) = rest.split_once(':')?;
+
+    if !matches!(operation, "INSERT" | "UPDATE" | "DELETE") {
+        return None;
+    }
+
+    let (old, new) = parse_tuples(rest.trim_start())?;
+
+    let (old_values, new_values) = match operation {
+        "INSERT" => (None, new),
+        "DELETE" => (new, None),
+        _ => (old, new),
+    };
+
+    Some(DecodedChange {
+        schema,
+        table,
+        operation: operation.to_string(),
+        old_values,
+        new_values,
+        xid: None,
+    })
+}
+
+/// Parse a transaction start line, e.g. `BEGIN 1234`, into its xid
+pub fn parse_begin(line: &str) -> Option<i64> {
+    line.strip_prefix("BEGIN ")?.trim().parse().ok()
+}
+
+/// Parse a transaction end line into its xid and commit time (when included),
+/// e.g. `COMMIT 1234 (at 2025-01-02 03:04:05.123456+00)`
+pub fn parse_commit(line: &str) -> Option<(i64, Option<DateTime<Utc>>)> {
+    let rest = line.strip_prefix("COMMIT ")?;
+    let (xid, at) = match rest.split_once(' ') {
+        Some((xid, at)) => (xid, Some(at)),
+        None => (rest, None),
+    };
+    let committed_at = at
+        .and_then(|at| at.strip_prefix("(at ")?.strip_suffix(')'))
+        .and_then(
+            |at| match Value::from_text("timestamp with time zone", at) {
+                Value::TimestampTz(t) => Some(t),
+                _ => None,
+            },
+        );
+    Some((xid.parse().ok()?, committed_at))
+}
+
+/// Parse a TRUNCATE line into a change without rows for each truncated table
+///
+/// e.g. `table public.a, public.b: TRUNCATE: restart_seqs`
+pub fn parse_truncate(line: &str) -> Option<Vec<DecodedChange>> {
+    let mut rest = line.strip_prefix("table ")?;
+    let mut changes = Vec::new();
+    loop {
+        let (schema, after) = parse_identifier(rest)?;
+        let (table, after) = parse_identifier(after.strip_prefix('.')?)?;
+        changes.push(DecodedChange {
+            schema,
+            table,
+            operation: "TRUNCATE".to_string(),
+            old_values: None,
+            new_values: None,
+            xid: None,
+        });
+        match after.strip_prefix(", ") {
+            Some(next) => rest = next,
+            None => {
+                after.strip_prefix(": TRUNCATE:")?;
+                return Some(changes);
+            }
+        }
+    }
+}
+
+/// Parse tuple data into (old-key, new-tuple) rows
+fn parse_tuples(mut input: &str) -> Option<(Option<RowData>, Option<RowData>)> {
+    if input.starts_with("(no-tuple-data)") {
+        return Some((None, None));
+    }
+
+    let mut old = RowData::new();
+    let mut new = RowData::new();
+    let mut section = Section::New;
+    let mut saw_old = false;
+
+    while !input.is_empty() {
+        if let Some(rest) = input.strip_prefix("old-key:") {
+            section = Section::Old;
+            saw_old = true;
+            input = rest.trim_start();
+            continue;
+        }
+        if let Some(rest) = input.strip_prefix("new-tuple:") {
+            section = Section::New;
+            input = rest.trim_start();
+            continue;
+        }
+
+        let (name, rest) = parse_identifier(input)?;
+        let rest = rest.strip_prefix('[')?;
+        let type_end = rest.find("]:")?;
+        let (value, rest) = parse_value(&rest[type_end + 2..], &rest[..type_end])?;
+
+        if let Some(value) = value {
+            match section {
+                Section::Old => old.insert(name, value),
+                Section::New => new.insert(name, value),
+            };
+        }
+        input = rest.trim_start();
+    }
+
+    let old = if saw_old { Some(old) } else { None };
+    Some((old, Some(new)))
+}
+
+/// Parse a possibly double-quoted identifier
+fn parse_identifier(input: &str) -> Option<(String, &str)> {
+    if let Some(rest) = input.strip_prefix('"') {
+        let mut name = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c == '"' {
+                if rest[i + 1..].starts_with('"') {
+                    name.push('"');
+                    chars.next();
+                } else {
+                    return Some((name, &rest[i + 1..]));
+                }
+            } else {
+                name.push(c);
+            }
+        }
+        None
+    } else {
+        let end = input.find(['.', ':', '[', ' ', ',']).unwrap_or(input.len());
+        if end == 0 {
+            return None;
+        }
+        Some((input[..end].to_string(), &input[end..]))
+    }
+}
+
+/// Parse a column value of the given type (`None` means the value was not sent)
+fn parse_value<'a>(input: &'a str, type_name: &str) -> Option<(Option<Value>, &'a str)> {
+    if let Some(rest) = input.strip_prefix('\'') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c == '\'' {
+                if rest[i + 1..].starts_with('\'') {
+                    value.push('\'');
+                    chars.next();
+                } else {
+                    return Some((Some(Value::from_text(type_name, &value)), &rest[i + 1..]));
+                }
+            } else {
+                value.push(c);
+            }
+        }
+        None
+    } else {
+        let end = input.find(' ').unwrap_or(input.len());
+        let raw = &input[..end];
+        let value = match raw {
+            UNCHANGED_TOAST => None,
+            "null" => Some(Value::Null),
+            _ => Some(Value::from_text(type_name, raw)),
+        };
+        Some((value, &input[end..]))
+    }
+}
+
+/// Parse a capture trigger notification payload
+pub fn parse_capture_payload(payload: &str) -> Option<CapturePayload> {
+    let json: Json = serde_json::from_str(payload).ok()?;
+
+    let Some(operation) = json.get("op").and_then(|v| v.as_str()) else {
+        return Some(CapturePayload::AuditId(json.get("id")?.as_i64()?));
+    };
+
+    Some(CapturePayload::Change(Box::new(DecodedChange {
+        schema: json.get("schema")?.as_str()?.to_string(),
+        table: json.get("table")?.as_str()?.to_string(),
+        operation: operation.to_string(),
+        old_values: json.get("old").and_then(json_to_row),
+        new_values: json.get("new").and_then(json_to_row),
+        xid: json.get("txid").and_then(|v| v.as_i64()),
+    })))
+}
+
+/// Convert a JSON object (from `to_jsonb(row)`) into row data
+pub fn json_to_row(value: &Json) -> Option<RowData> {
+    let object = value.as_object()?;
+    Some(
+        object
+            .iter()
+            .map(|(k, v)| (k.clone(), Value::from_json(v)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_string())
+    }
+
+    #[test]
+    fn test_parse_insert() {
+        let line = "table public.users: INSERT: id[integer]:1 name[text]:'O''Brien' bio[text]:null";
+        let change = parse_change(line).unwrap();
+
+        assert_eq!(change.schema, "public");
+        assert_eq!(change.table, "users");
+        assert_eq!(change.operation, "INSERT");
+        assert!(change.old_values.is_none());
+
+        let new = change.new_values.unwrap();
+        assert_eq!(new["id"], Value::Int(1));
+        assert_eq!(new["name"], text("O'Brien"));
+        assert_eq!(new["bio"], Value::Null);
+    }
+
+    #[test]
+    fn test_parse_update_with_old_key() {
+        let line = "table public.\"My T\": UPDATE: old-key: \"My Col\"[text]:'k' \
+                    new-tuple: \"My Col\"[text]:'k2' tags[text[]]:'{a,b}'";
+        let change = parse_change(line).unwrap();
+
+        assert_eq!(change.table, "My T");
+        assert_eq!(change.old_values.unwrap()["My Col"], text("k"));
+
+        let new = change.new_values.unwrap();
+        assert_eq!(new["My Col"], text("k2"));
+        assert_eq!(new["tags"], Value::Array(vec![text("a"), text("b")]));
+    }
+
+    #[test]
+    fn test_parse_delete_and_toast() {
+        let line = "table public.docs: DELETE: id[bigint]:7";
+        let change = parse_change(line).unwrap();
+        assert_eq!(change.old_values.unwrap()["id"], Value::Int(7));
+        assert!(change.new_values.is_none());
+
+        let line = "table public.docs: UPDATE: id[bigint]:7 body[text]:unchanged-toast-datum";
+        let new = parse_change(line).unwrap().new_values.unwrap();
+        assert!(!new.contains_key("body"));
+
+        let change = parse_change("table public.docs: DELETE: (no-tuple-data)").unwrap();
+        assert_eq!(change.operation, "DELETE");
+        assert!(change.old_values.is_none() && change.new_values.is_none());
+    }
+
+    #[test]
+    fn test_parse_capture_payload() {
+        let payload = r#"{"schema": "public", "table": "users", "op": "UPDATE",
+            "old": {"id": 1, "name": "Alice", "bio": null},
+            "new": {"id": 1, "name": "Alicia", "bio": null}}"#;
+
+        let Some(CapturePayload::Change(change)) = parse_capture_payload(payload) else {
+            panic!("expected inline change");
+        };
+        assert_eq!(change.operation, "UPDATE");
+        assert_eq!(change.old_values.unwrap()["name"], text("Alice"));
+
+        let new = change.new_values.unwrap();
+        assert_eq!(new["id"], Value::Int(1));
+        assert_eq!(new["bio"], Value::Null);
+
+        assert_eq!(
+            parse_capture_payload(r#"{"id": 42}"#),
+            Some(CapturePayload::AuditId(42))
+        );
+        assert_eq!(parse_capture_payload(r#"{"op": "INSERT"}"#), None);
+    }
+
+    #[test]
+    fn test_null_and_null_text() {
+        let line = "table public.t: INSERT: a[text]:'NULL' b[text]:null c[numeric]:1.50";
+        let new = parse_change(line).unwrap().new_values.unwrap();
+        assert_eq!(new["a"], text("NULL"));
+        assert_eq!(new["b"], Value::Null);
+        assert_eq!(new["c"], Value::Numeric("1.50".to_string()));
+    }
+
+    #[test]
+    fn test_skip_non_row_messages() {
+        assert!(parse_change("BEGIN 726").is_none());
+        assert!(parse_change("COMMIT 726 (at 2025-01-01 00:00:00+00)").is_none());
+        assert!(parse_change("table public.t: TRUNCATE: (no-flags)").is_none());
+    }
+
+    #[test]
+    fn test_parse_truncate() {
+        let changes =
+            parse_truncate("table public.a, \"My Schema\".b: TRUNCATE: restart_seqs").unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].operation, "TRUNCATE");
+        assert_eq!(
+            (changes[0].schema.as_str(), changes[0].table.as_str()),
+            ("public", "a")
+        );
+        assert_eq!(
+            (changes[1].schema.as_str(), changes[1].table.as_str()),
+            ("My Schema", "b")
+        );
+        assert!(changes[1].old_values.is_none() && changes[1].new_values.is_none());
+        assert!(parse_truncate("table public.t: INSERT: id[integer]:1").is_none());
+    }
+
+    #[test]
+    fn test_parse_transaction_markers() {
+        assert_eq!(parse_begin("BEGIN 1234"), Some(1234));
+        assert_eq!(parse_commit("COMMIT 1234"), Some((1234, None)));
+
+        let (xid, committed_at) =
+            parse_commit("COMMIT 1235 (at 2025-01-02 03:04:05.123456+09)").unwrap();
+        assert_eq!(xid, 1235);
+        assert_eq!(
+            committed_at.unwrap().to_rfc3339(),
+            "2025-01-01T18:04:05.123456+00:00"
+        );
+        assert!(parse_begin("table public.t: INSERT: id[integer]:1").is_none());
+    }
+}
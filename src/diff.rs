@@ -1,103 +1,272 @@
 //! Diff calculation module
 
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-use crate::types::{RowData, RowDiff};
+use crate::types::{DefinitionDiff, DiffScope, RowData, RowDiff, TableMetadata};
+use crate::value::Value;
 
-/// Get primary key value from row (with fallback)
-pub fn get_pk_value(row: &RowData, pk_col: &str) -> String {
-    // Try the specified PK column
-    if let Some(v) = row.get(pk_col) {
-        if !v.is_empty() && v != "NULL" {
-            return v.clone();
-        }
+/// Get primary key values from row
+pub fn get_pk_value(row: &RowData, pk_cols: &[String]) -> Vec<String> {
+    pk_cols
+        .iter()
+        .map(|col| row.get(col).map(|v| v.to_string()).unwrap_or_default())
+        .collect()
+}
+
+/// Fingerprint of a full row (column order independent)
+fn row_fingerprint(row: &RowData) -> String {
+    let mut pairs: Vec<(&String, &Value)> = row.iter().collect();
+    pairs.sort_by(|a, b| a.0.cmp(b.0));
+    pairs
+        .iter()
+        .map(|(k, v)| format!("{}\u{1f}{:?}", k, v))
+        .collect::<Vec<_>>()
+        .join("\u{1e}")
+}
+
+/// Calculate all diffs between old and new snapshots
+///
+/// Both sides are merged in one pass over their key order, so diffs come
+/// out in key order. Rows fetched from PostgreSQL already arrive in that
+/// order and are merged as they are, without copying keys; only input in
+/// another order (see `key_order`) is sorted first.
+/// Tables without a row identity (empty `pk_cols`) are compared as multisets.
+/// With `DiffScope::Slice`, `new_rows` only holds recently changed rows, so
+/// rows missing from it are not reported as removed.
+pub fn calculate_all_diffs(
+    old_rows: &[RowData],
+    new_rows: &[RowData],
+    pk_cols: &[String],
+    scope: DiffScope,
+) -> Vec<RowDiff> {
+    if pk_cols.is_empty() {
+        return calculate_multiset_diffs(old_rows, new_rows, scope);
     }
 
-    // Fallback: try common PK column names
-    for fallback in &["id", "uuid", "pk"] {
-        if let Some(v) = row.get(*fallback) {
-            if !v.is_empty() && v != "NULL" {
-                return v.clone();
+    let old_order = key_order(old_rows, pk_cols);
+    let new_order = key_order(new_rows, pk_cols);
+    let old_at = |i: usize| old_order.as_ref().map_or(&old_rows[i], |order| order[i]);
+    let new_at = |j: usize| new_order.as_ref().map_or(&new_rows[j], |order| order[j]);
+
+    let mut diffs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old_rows.len() || j < new_rows.len() {
+        let order = match (i < old_rows.len(), j < new_rows.len()) {
+            (true, true) => compare_row_keys(old_at(i), new_at(j), pk_cols),
+            (true, false) => Ordering::Less,
+            (false, _) => Ordering::Greater,
+        };
+
+        match order {
+            // DELETE: in old but not in new
+            Ordering::Less => {
+                let old_row = old_at(i);
+                if scope == DiffScope::Full {
+                    diffs.push(RowDiff {
+                        table: String::new(),
+                        pk_columns: pk_cols.to_vec(),
+                        pk_values: get_pk_value(old_row, pk_cols),
+                        change_type: "removed".to_string(),
+                        old_values: Some(old_row.clone()),
+                        new_values: None,
+                        changed_columns: old_row.keys().cloned().collect(),
+                        xid: None,
+                    });
+                }
+                i += 1;
+            }
+            // INSERT: in new but not in old
+            Ordering::Greater => {
+                let new_row = new_at(j);
+                diffs.push(RowDiff {
+                    table: String::new(),
+                    pk_columns: pk_cols.to_vec(),
+                    pk_values: get_pk_value(new_row, pk_cols),
+                    change_type: "added".to_string(),
+                    old_values: None,
+                    new_values: Some(new_row.clone()),
+                    changed_columns: new_row.keys().cloned().collect(),
+                    xid: None,
+                });
+                j += 1;
+            }
+            // UPDATE: in both but values differ
+            Ordering::Equal => {
+                let (old_row, new_row) = (old_at(i), new_at(j));
+                let changed_cols: Vec<String> = new_row
+                    .iter()
+                    .filter(|(col, new_val)| old_row.get(*col) != Some(*new_val))
+                    .map(|(col, _)| col.clone())
+                    .collect();
+
+                if !changed_cols.is_empty() {
+                    diffs.push(RowDiff {
+                        table: String::new(),
+                        pk_columns: pk_cols.to_vec(),
+                        pk_values: get_pk_value(new_row, pk_cols),
+                        change_type: "modified".to_string(),
+                        old_values: Some(old_row.clone()),
+                        new_values: Some(new_row.clone()),
+                        changed_columns: changed_cols,
+                        xid: None,
+                    });
+                }
+                i += 1;
+                j += 1;
             }
         }
     }
 
-    // Last fallback: use first non-NULL value as identifier
-    for (col, val) in row {
-        if !val.is_empty() && val != "NULL" {
-            return format!("{}:{}", col, val);
-        }
+    diffs
+}
+
+/// Order of rows by key, or `None` when they are already in key order
+///
+/// Rows fetched in row identity order are, except for keys PostgreSQL
+/// orders differently from `Value::sort_cmp` (text under a collation other
+/// than `C`, enums, ...). Those and query results are sorted here.
+fn key_order<'a>(rows: &'a [RowData], pk_cols: &[String]) -> Option<Vec<&'a RowData>> {
+    if rows
+        .windows(2)
+        .all(|w| compare_row_keys(&w[0], &w[1], pk_cols).is_le())
+    {
+        return None;
     }
+    let mut order: Vec<&RowData> = rows.iter().collect();
+    order.sort_by(|a, b| compare_row_keys(a, b, pk_cols));
+    Some(order)
+}
 
-    // Absolute fallback: hash of values
-    let hash: String = row.values().take(3).cloned().collect::<Vec<_>>().join("_");
-    format!("row_{}", hash.chars().take(20).collect::<String>())
+/// Compare two rows by their key columns
+pub fn compare_row_keys(a: &RowData, b: &RowData, pk_cols: &[String]) -> Ordering {
+    pk_cols
+        .iter()
+        .map(|col| {
+            let x = a.get(col).unwrap_or(&Value::Null);
+            let y = b.get(col).unwrap_or(&Value::Null);
+            x.sort_cmp(y)
+        })
+        .find(|order| order.is_ne())
+        .unwrap_or(Ordering::Equal)
 }
 
-/// Calculate all diffs between old and new snapshots
-pub fn calculate_all_diffs(
-    old_rows: &[RowData],
-    new_rows: &[RowData],
-    pk_col: &str,
+/// Calculate diffs from row hashes
+///
+/// `old` maps identity values (as text) to row hashes and `current` lists the
+/// current identity values and hashes. `changed_rows` holds the full rows
+/// whose hash is new; rows missing from it are skipped. Old row images are
+/// not kept, so removed rows carry only their identity (typed by `pk_types`)
+/// and updates only their new values, with no changed columns.
+pub fn calculate_hash_diffs(
+    old: &HashMap<Vec<String>, String>,
+    current: &[(Vec<String>, String)],
+    changed_rows: &[RowData],
+    pk_cols: &[String],
+    pk_types: &[String],
 ) -> Vec<RowDiff> {
     let mut diffs = Vec::new();
 
-    // Build lookup maps by PK
-    let old_by_pk: HashMap<String, &RowData> = old_rows
-        .iter()
-        .map(|r| (get_pk_value(r, pk_col), r))
-        .collect();
-    let new_by_pk: HashMap<String, &RowData> = new_rows
+    let rows_by_pk: HashMap<Vec<String>, &RowData> = changed_rows
         .iter()
-        .map(|r| (get_pk_value(r, pk_col), r))
+        .map(|r| (get_pk_value(r, pk_cols), r))
         .collect();
 
-    // INSERT: in new but not in old
-    for (pk, new_row) in &new_by_pk {
-        if !old_by_pk.contains_key(pk) {
-            diffs.push(RowDiff {
-                pk_column: pk_col.to_string(),
-                pk_value: pk.clone(),
-                change_type: "added".to_string(),
-                old_values: None,
-                new_values: Some((*new_row).clone()),
-                changed_columns: new_row.keys().cloned().collect(),
-            });
+    for (pk, hash) in current {
+        let old_hash = old.get(pk);
+        if old_hash == Some(hash) {
+            continue;
         }
+        let Some(row) = rows_by_pk.get(pk) else {
+            continue;
+        };
+
+        // Which columns of an updated row changed is unknown
+        let (change_type, changed_columns) = match old_hash {
+            None => ("added", row.keys().cloned().collect()),
+            Some(_) => ("modified", Vec::new()),
+        };
+        diffs.push(RowDiff {
+            table: String::new(),
+            pk_columns: pk_cols.to_vec(),
+            pk_values: pk.clone(),
+            change_type: change_type.to_string(),
+            old_values: None,
+            new_values: Some((*row).clone()),
+            changed_columns,
+            xid: None,
+        });
     }
 
-    // DELETE: in old but not in new
-    for (pk, old_row) in &old_by_pk {
-        if !new_by_pk.contains_key(pk) {
+    let current_pks: HashSet<&Vec<String>> = current.iter().map(|(pk, _)| pk).collect();
+    for pk in old.keys() {
+        if !current_pks.contains(pk) {
+            let identity: RowData = pk_cols
+                .iter()
+                .cloned()
+                .zip(
+                    pk_types
+                        .iter()
+                        .zip(pk)
+                        .map(|(type_name, v)| Value::from_text(type_name, v)),
+                )
+                .collect();
             diffs.push(RowDiff {
-                pk_column: pk_col.to_string(),
-                pk_value: pk.clone(),
+                table: String::new(),
+                pk_columns: pk_cols.to_vec(),
+                pk_values: pk.clone(),
                 change_type: "removed".to_string(),
-                old_values: Some((*old_row).clone()),
+                old_values: Some(identity),
                 new_values: None,
-                changed_columns: old_row.keys().cloned().collect(),
+                changed_columns: pk_cols.to_vec(),
+                xid: None,
             });
         }
     }
 
-    // UPDATE: in both but values differ
-    for (pk, new_row) in &new_by_pk {
-        if let Some(old_row) = old_by_pk.get(pk) {
-            let changed_cols: Vec<String> = new_row
-                .iter()
-                .filter(|(col, new_val)| old_row.get(*col).map(|o| o != *new_val).unwrap_or(true))
-                .map(|(col, _)| col.clone())
-                .collect();
+    diffs
+}
 
-            if !changed_cols.is_empty() {
-                diffs.push(RowDiff {
-                    pk_column: pk_col.to_string(),
-                    pk_value: pk.clone(),
-                    change_type: "modified".to_string(),
-                    old_values: Some((*old_row).clone()),
-                    new_values: Some((*new_row).clone()),
-                    changed_columns: changed_cols,
-                });
+/// Calculate diffs for tables without row identity
+///
+/// Rows are compared as multisets of full-row fingerprints, so identical rows
+/// are counted rather than merged. Changes are only ever reported as added
+/// or removed rows, never as updates.
+pub fn calculate_multiset_diffs(
+    old_rows: &[RowData],
+    new_rows: &[RowData],
+    scope: DiffScope,
+) -> Vec<RowDiff> {
+    // Remaining count per fingerprint: positive = only in old, negative = only in new
+    let mut balance: HashMap<String, i64> = HashMap::new();
+    for row in old_rows {
+        *balance.entry(row_fingerprint(row)).or_default() += 1;
+    }
+    for row in new_rows {
+        *balance.entry(row_fingerprint(row)).or_default() -= 1;
+    }
+
+    let mut diffs = Vec::new();
+
+    for row in new_rows {
+        if let Some(count) = balance.get_mut(&row_fingerprint(row)) {
+            if *count < 0 {
+                *count += 1;
+                diffs.push(keyless_diff("added", row));
+            }
+        }
+    }
+
+    if scope == DiffScope::Slice {
+        return diffs;
+    }
+
+    for row in old_rows {
+        if let Some(count) = balance.get_mut(&row_fingerprint(row)) {
+            if *count > 0 {
+                *count -= 1;
+                diffs.push(keyless_diff("removed", row));
             }
         }
     }
@@ -105,16 +274,327 @@ pub fn calculate_all_diffs(
     diffs
 }
 
+/// Create an added/removed diff for a row without identity
+fn keyless_diff(change_type: &str, row: &RowData) -> RowDiff {
+    let (old_values, new_values) = if change_type == "added" {
+        (None, Some(row.clone()))
+    } else {
+        (Some(row.clone()), None)
+    };
+
+    RowDiff {
+        table: String::new(),
+        pk_columns: Vec::new(),
+        pk_values: Vec::new(),
+        change_type: change_type.to_string(),
+        old_values,
+        new_values,
+        changed_columns: row.keys().cloned().collect(),
+        xid: None,
+    }
+}
+
+/// Turn removed and added rows that differ only by key into key changes
+///
+/// Updating a key column makes the old key disappear and a new one appear.
+/// A removed row is paired with the added row having the same values in
+/// every other column, when neither can be paired with another row. At most
+/// `limit` pairs are made (the updates the diffs do not account for); the
+/// key change is kept at the position of the added row.
+pub fn pair_key_changes(diffs: Vec<RowDiff>, limit: usize) -> Vec<RowDiff> {
+    if limit == 0 {
+        return diffs;
+    }
+
+    let non_key = |diff: &RowDiff, row: &Option<RowData>| -> Option<String> {
+        let row = row.as_ref()?;
+        let mut values = row.clone();
+        values.retain(|k, _| !diff.pk_columns.contains(k));
+        Some(format!("{}\u{1d}{}", diff.table, row_fingerprint(&values)))
+    };
+    let mut removed: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut added: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, diff) in diffs.iter().enumerate() {
+        if diff.pk_columns.is_empty() {
+            continue;
+        }
+        let (rows, row) = match diff.change_type.as_str() {
+            "removed" => (&mut removed, &diff.old_values),
+            "added" => (&mut added, &diff.new_values),
+            _ => continue,
+        };
+        if let Some(values) = non_key(diff, row) {
+            rows.entry(values).or_default().push(i);
+        }
+    }
+
+    // Pair only unambiguous matches, in key order of the removed rows
+    let mut pairs: Vec<(usize, usize)> = removed
+        .iter()
+        .filter_map(
+            |(values, old)| match (old.as_slice(), added.get(values)?.as_slice()) {
+                ([old], [new]) => Some((*old, *new)),
+                _ => None,
+            },
+        )
+        .collect();
+    pairs.sort();
+    pairs.truncate(limit);
+
+    let mut slots: Vec<Option<RowDiff>> = diffs.into_iter().map(Some).collect();
+    for (old, new) in pairs {
+        let (Some(removed), Some(added)) = (slots[old].take(), slots[new].take()) else {
+            continue;
+        };
+        let old_values = removed.old_values;
+        let new_values = added.new_values;
+        let changed_columns = added
+            .pk_columns
+            .iter()
+            .filter(|col| {
+                let old = old_values.as_ref().and_then(|row| row.get(*col));
+                let new = new_values.as_ref().and_then(|row| row.get(*col));
+                old != new
+            })
+            .cloned()
+            .collect();
+        slots[new] = Some(RowDiff {
+            change_type: "modified".to_string(),
+            old_values,
+            new_values,
+            changed_columns,
+            xid: added.xid.or(removed.xid),
+            ..added
+        });
+    }
+    slots.into_iter().flatten().collect()
+}
+
+/// Parent of a changed row among the other changed rows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowParent {
+    /// Index of the parent row diff
+    pub index: usize,
+    /// Whether the row was deleted by a cascade from its deleted parent
+    pub cascade: Cascade,
+}
+
+/// Whether a deleted row was deleted by a cascade from its deleted parent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cascade {
+    /// The foreign key does not cascade, a row was not deleted, or the
+    /// rows were deleted in different transactions
+    No,
+    /// Both rows were deleted in the same transaction
+    Certain,
+    /// Both rows were deleted, in transactions that are not known
+    Possible,
+}
+
+/// Referenced table, referenced columns and their values in a row
+type ReferencedKey<'a> = (&'a str, &'a [String], Vec<String>);
+
+/// Find the changed row each changed row references through a foreign key
+///
+/// Rows are linked to a changed row of the referenced table whose old or
+/// new values match their foreign key columns (the first foreign key that
+/// matches wins). Links that would form a cycle are dropped.
+pub fn find_parent_rows(diffs: &[RowDiff], metadata: &TableMetadata) -> Vec<Option<RowParent>> {
+    let values = |row: Option<&RowData>, cols: &[String]| -> Option<Vec<String>> {
+        let row = row?;
+        cols.iter()
+            .map(|col| match row.get(col) {
+                None | Some(Value::Null) => None,
+                Some(value) => Some(value.to_string()),
+            })
+            .collect()
+    };
+
+    // Referenced columns of each table that changed rows may reference
+    let mut referenced: HashMap<&str, HashSet<&[String]>> = HashMap::new();
+    for fk in diffs
+        .iter()
+        .filter_map(|diff| metadata.get(&diff.table))
+        .flat_map(|meta| &meta.foreign_keys)
+    {
+        referenced
+            .entry(fk.referenced_table.as_str())
+            .or_default()
+            .insert(fk.referenced_columns.as_slice());
+    }
+
+    // Changed rows by table, referenced columns and their values
+    let mut rows_by_key: HashMap<ReferencedKey, Vec<usize>> = HashMap::new();
+    for (j, parent) in diffs.iter().enumerate() {
+        let Some(column_sets) = referenced.get(parent.table.as_str()) else {
+            continue;
+        };
+        for &cols in column_sets {
+            for row in [parent.new_values.as_ref(), parent.old_values.as_ref()] {
+                if let Some(key) = values(row, cols) {
+                    let rows = rows_by_key
+                        .entry((parent.table.as_str(), cols, key))
+                        .or_default();
+                    if rows.last() != Some(&j) {
+                        rows.push(j);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut parents: Vec<Option<RowParent>> = diffs
+        .iter()
+        .enumerate()
+        .map(|(i, child)| {
+            let meta = metadata.get(&child.table)?;
+            let row = child.new_values.as_ref().or(child.old_values.as_ref());
+            meta.foreign_keys.iter().find_map(|fk| {
+                let key = (
+                    fk.referenced_table.as_str(),
+                    fk.referenced_columns.as_slice(),
+                    values(row, &fk.columns)?,
+                );
+                let index = *rows_by_key.get(&key)?.iter().find(|&&j| j != i)?;
+                let parent = &diffs[index];
+                let removed = |diff: &RowDiff| diff.change_type == "removed";
+                let cascade = if !fk.on_delete_cascade || !removed(child) || !removed(parent) {
+                    Cascade::No
+                } else {
+                    match (child.xid, parent.xid) {
+                        (Some(a), Some(b)) if a == b => Cascade::Certain,
+                        (Some(_), Some(_)) => Cascade::No,
+                        _ => Cascade::Possible,
+                    }
+                };
+                Some(RowParent { index, cascade })
+            })
+        })
+        .collect();
+
+    // A row cannot be nested under itself: cycles are cut at their first row
+    let mut done = vec![false; parents.len()];
+    let mut on_path = vec![false; parents.len()];
+    for start in 0..parents.len() {
+        let mut path = Vec::new();
+        let mut current = Some(start);
+        while let Some(i) = current.filter(|&i| !done[i]) {
+            if on_path[i] {
+                let cycle_start = path.iter().position(|&j| j == i).unwrap_or(0);
+                if let Some(&first) = path[cycle_start..].iter().min() {
+                    parents[first] = None;
+                }
+                break;
+            }
+            on_path[i] = true;
+            path.push(i);
+            current = parents[i].map(|p| p.index);
+        }
+        for i in path {
+            done[i] = true;
+        }
+    }
+    parents
+}
+
+/// Calculate changes between two sets of catalog object definitions
+pub fn calculate_definition_diffs(
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+) -> Vec<DefinitionDiff> {
+    let removed = old
+        .iter()
+        .filter(|(object, _)| !new.contains_key(*object))
+        .map(|(object, definition)| ("removed", object, Some(definition), None));
+    let added_or_modified = new
+        .iter()
+        .filter_map(|(object, definition)| match old.get(object) {
+            None => Some(("added", object, None, Some(definition))),
+            Some(previous) if previous != definition => {
+                Some(("modified", object, Some(previous), Some(definition)))
+            }
+            Some(_) => None,
+        });
+
+    let mut diffs: Vec<DefinitionDiff> = removed
+        .chain(added_or_modified)
+        .map(
+            |(change_type, object, old_definition, new_definition)| DefinitionDiff {
+                object: object.clone(),
+                change_type: change_type.to_string(),
+                old_definition: old_definition.cloned(),
+                new_definition: new_definition.cloned(),
+            },
+        )
+        .collect();
+    diffs.sort_by(|a, b| a.object.cmp(&b.object));
+    diffs
+}
+
+/// Line of a text diff
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineDiff {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Diff two texts line by line (longest common subsequence)
+pub fn diff_lines(old: &str, new: &str) -> Vec<LineDiff> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j]: length of the common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(LineDiff::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(LineDiff::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(LineDiff::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    lines
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Build a row, with integers typed as such (as fetched from integer columns)
     fn create_row(data: &[(&str, &str)]) -> RowData {
         data.iter()
-            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .map(|(k, v)| {
+                let value = match v.parse() {
+                    Ok(n) => Value::Int(n),
+                    Err(_) => Value::Text(v.to_string()),
+                };
+                (k.to_string(), value)
+            })
             .collect()
     }
 
+    fn pk(cols: &[&str]) -> Vec<String> {
+        cols.iter().map(|c| c.to_string()).collect()
+    }
+
     #[test]
     fn test_detect_insert() {
         let old_rows = vec![create_row(&[("id", "1"), ("name", "Alice")])];
@@ -123,10 +603,10 @@ mod tests {
             create_row(&[("id", "2"), ("name", "Bob")]),
         ];
 
-        let diffs = calculate_all_diffs(&old_rows, &new_rows, "id");
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &pk(&["id"]), DiffScope::Full);
         assert_eq!(diffs.len(), 1);
         assert_eq!(diffs[0].change_type, "added");
-        assert_eq!(diffs[0].pk_value, "2");
+        assert_eq!(diffs[0].pk_values, vec!["2"]);
     }
 
     #[test]
@@ -137,10 +617,10 @@ mod tests {
         ];
         let new_rows = vec![create_row(&[("id", "1"), ("name", "Alice")])];
 
-        let diffs = calculate_all_diffs(&old_rows, &new_rows, "id");
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &pk(&["id"]), DiffScope::Full);
         assert_eq!(diffs.len(), 1);
         assert_eq!(diffs[0].change_type, "removed");
-        assert_eq!(diffs[0].pk_value, "2");
+        assert_eq!(diffs[0].pk_values, vec!["2"]);
     }
 
     #[test]
@@ -148,9 +628,472 @@ mod tests {
         let old_rows = vec![create_row(&[("id", "1"), ("name", "Alice")])];
         let new_rows = vec![create_row(&[("id", "1"), ("name", "Alicia")])];
 
-        let diffs = calculate_all_diffs(&old_rows, &new_rows, "id");
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &pk(&["id"]), DiffScope::Full);
         assert_eq!(diffs.len(), 1);
         assert_eq!(diffs[0].change_type, "modified");
         assert!(diffs[0].changed_columns.contains(&"name".to_string()));
     }
+
+    #[test]
+    fn test_composite_primary_key() {
+        let old_rows = vec![
+            create_row(&[("user_id", "1"), ("role_id", "7"), ("note", "a")]),
+            create_row(&[("user_id", "1"), ("role_id", "8"), ("note", "b")]),
+        ];
+        let new_rows = vec![
+            create_row(&[("user_id", "1"), ("role_id", "7"), ("note", "a")]),
+            create_row(&[("user_id", "1"), ("role_id", "8"), ("note", "c")]),
+            create_row(&[("user_id", "2"), ("role_id", "7"), ("note", "d")]),
+        ];
+
+        let mut diffs = calculate_all_diffs(
+            &old_rows,
+            &new_rows,
+            &pk(&["user_id", "role_id"]),
+            DiffScope::Full,
+        );
+        diffs.sort_by(|a, b| a.pk_values.cmp(&b.pk_values));
+
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].change_type, "modified");
+        assert_eq!(diffs[0].pk_values, vec!["1", "8"]);
+        assert_eq!(diffs[1].change_type, "added");
+        assert_eq!(diffs[1].pk_values, vec!["2", "7"]);
+    }
+
+    #[test]
+    fn test_multiset_keeps_duplicate_rows() {
+        let a = create_row(&[("level", "info"), ("msg", "started")]);
+        let b = create_row(&[("level", "warn"), ("msg", "slow")]);
+        let c = create_row(&[("level", "info"), ("msg", "done")]);
+
+        let old_rows = vec![a.clone(), a.clone(), b.clone()];
+        let new_rows = vec![a.clone(), b.clone(), b.clone(), c.clone()];
+
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &[], DiffScope::Full);
+        let added: Vec<_> = diffs.iter().filter(|d| d.change_type == "added").collect();
+        let removed: Vec<_> = diffs
+            .iter()
+            .filter(|d| d.change_type == "removed")
+            .collect();
+
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(added.len(), 2);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].old_values.as_ref(), Some(&a));
+        assert!(diffs.iter().all(|d| d.change_type != "modified"));
+    }
+
+    #[test]
+    fn test_multiset_never_reports_update() {
+        let old_rows = vec![create_row(&[("msg", "hello")])];
+        let new_rows = vec![create_row(&[("msg", "hullo")])];
+
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &[], DiffScope::Full);
+        assert_eq!(diffs.len(), 2);
+        assert!(diffs.iter().all(|d| d.pk_values.is_empty()));
+        assert!(diffs.iter().all(|d| d.change_type != "modified"));
+    }
+
+    #[test]
+    fn test_slice_scope_ignores_missing_rows() {
+        let old_rows = vec![
+            create_row(&[("id", "1"), ("status", "new")]),
+            create_row(&[("id", "2"), ("status", "new")]),
+        ];
+        // Only recently changed rows are fetched
+        let slice = vec![
+            create_row(&[("id", "2"), ("status", "done")]),
+            create_row(&[("id", "3"), ("status", "new")]),
+        ];
+
+        let diffs = calculate_all_diffs(&old_rows, &slice, &pk(&["id"]), DiffScope::Slice);
+        assert_eq!(diffs.len(), 2);
+        assert!(diffs.iter().all(|d| d.change_type != "removed"));
+        assert!(diffs
+            .iter()
+            .any(|d| d.change_type == "modified" && d.pk_values == vec!["2"]));
+        assert!(diffs
+            .iter()
+            .any(|d| d.change_type == "added" && d.pk_values == vec!["3"]));
+    }
+
+    #[test]
+    fn test_hash_diffs() {
+        let hashes = |pairs: &[(&str, &str)]| -> Vec<(Vec<String>, String)> {
+            pairs
+                .iter()
+                .map(|(id, h)| (vec![id.to_string()], h.to_string()))
+                .collect()
+        };
+        let old: HashMap<Vec<String>, String> = hashes(&[("1", "aa"), ("2", "bb"), ("3", "cc")])
+            .into_iter()
+            .collect();
+        let current = hashes(&[("1", "aa"), ("2", "b2"), ("4", "dd"), ("5", "ee")]);
+        // Row 5 changed again before its full row was fetched
+        let changed_rows = vec![
+            create_row(&[("id", "2"), ("name", "Bob")]),
+            create_row(&[("id", "4"), ("name", "Dave")]),
+        ];
+
+        let diffs = calculate_hash_diffs(
+            &old,
+            &current,
+            &changed_rows,
+            &pk(&["id"]),
+            &pk(&["integer"]),
+        );
+        assert_eq!(diffs.len(), 3);
+
+        let find = |id: &str| diffs.iter().find(|d| d.pk_values == vec![id]).unwrap();
+        assert_eq!(find("2").change_type, "modified");
+        assert!(find("2").old_values.is_none());
+        // Previous values are not kept, so changed columns are unknown
+        assert!(find("2").changed_columns.is_empty());
+        assert_eq!(find("4").change_type, "added");
+        assert_eq!(find("3").change_type, "removed");
+        // Removed keys are typed like fetched ones
+        let removed = find("3").old_values.as_ref().unwrap();
+        assert_eq!(removed.get("id"), Some(&Value::Int(3)));
+    }
+
+    #[test]
+    fn test_diffs_in_numeric_key_order() {
+        let old_rows = vec![create_row(&[("id", "1"), ("name", "Alice")])];
+        let new_rows = vec![
+            create_row(&[("id", "10"), ("name", "Judy")]),
+            create_row(&[("id", "2"), ("name", "Bob")]),
+            create_row(&[("id", "1"), ("name", "Alicia")]),
+        ];
+
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &pk(&["id"]), DiffScope::Full);
+        let ids: Vec<&str> = diffs.iter().map(|d| d.pk_values[0].as_str()).collect();
+        assert_eq!(ids, vec!["1", "2", "10"]);
+        assert_eq!(diffs[0].change_type, "modified");
+    }
+
+    #[test]
+    fn test_large_table_diff() {
+        let row = |id: usize, name: &str| create_row(&[("id", &id.to_string()), ("name", name)]);
+        let old_rows: Vec<RowData> = (1..=10_000).map(|id| row(id, "same")).collect();
+
+        // Delete ids 1..=50, update every 100th id, insert 10001..=10050, shuffled order
+        let mut new_rows: Vec<RowData> = (51..=10_050)
+            .map(|id| row(id, if id % 100 == 0 { "changed" } else { "same" }))
+            .collect();
+        new_rows.reverse();
+
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &pk(&["id"]), DiffScope::Full);
+        let count = |t: &str| diffs.iter().filter(|d| d.change_type == t).count();
+
+        assert_eq!(count("removed"), 50);
+        assert_eq!(count("added"), 50);
+        assert_eq!(count("modified"), 100);
+        assert!(diffs.windows(2).all(|w| {
+            let key = |d: &RowDiff| d.new_values.clone().or(d.old_values.clone()).unwrap();
+            compare_row_keys(&key(&w[0]), &key(&w[1]), &pk(&["id"])).is_lt()
+        }));
+    }
+
+    #[test]
+    fn test_key_order() {
+        let ids = |ids: &[&str]| -> Vec<RowData> {
+            ids.iter().map(|id| create_row(&[("id", id)])).collect()
+        };
+
+        // Rows fetched in key order are merged as they are
+        assert!(key_order(&ids(&["1", "2", "10"]), &pk(&["id"])).is_none());
+        assert!(key_order(&ids(&["a", "b", "B"]), &pk(&["id"])).is_some());
+        assert!(key_order(&ids(&["B", "a", "b"]), &pk(&["id"])).is_none());
+
+        // Text keys in another collation order are sorted before the merge
+        let old_rows = ids(&["a", "B"]);
+        let new_rows = ids(&["a", "b", "B"]);
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &pk(&["id"]), DiffScope::Full);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].change_type, "added");
+        assert_eq!(diffs[0].pk_values, vec!["b"]);
+    }
+
+    #[test]
+    fn test_null_differs_from_null_text() {
+        let mut old_row = create_row(&[("id", "1")]);
+        old_row.insert("note".to_string(), Value::Null);
+        let new_row = create_row(&[("id", "1"), ("note", "NULL")]);
+
+        let diffs = calculate_all_diffs(&[old_row], &[new_row], &pk(&["id"]), DiffScope::Full);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].changed_columns, vec!["note"]);
+    }
+
+    #[test]
+    fn test_float_nan_and_negative_zero() {
+        let row = |value: f64| {
+            let mut row = create_row(&[("id", "1")]);
+            row.insert("score".to_string(), Value::Float(value));
+            row
+        };
+
+        // NaN read again is unchanged
+        let diffs = calculate_all_diffs(
+            &[row(f64::NAN)],
+            &[row(f64::NAN)],
+            &pk(&["id"]),
+            DiffScope::Full,
+        );
+        assert!(diffs.is_empty());
+
+        let diffs = calculate_all_diffs(&[row(0.0)], &[row(-0.0)], &pk(&["id"]), DiffScope::Full);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].changed_columns, vec!["score"]);
+    }
+
+    #[test]
+    fn test_changed_columns_in_table_order() {
+        let old_rows = vec![create_row(&[
+            ("id", "1"),
+            ("b", "1"),
+            ("a", "1"),
+            ("c", "1"),
+        ])];
+        let new_rows = vec![create_row(&[
+            ("id", "1"),
+            ("b", "1"),
+            ("a", "2"),
+            ("c", "2"),
+        ])];
+
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &pk(&["id"]), DiffScope::Full);
+        assert_eq!(diffs[0].changed_columns, vec!["a", "c"]);
+        let columns: Vec<&String> = diffs[0].new_values.as_ref().unwrap().keys().collect();
+        assert_eq!(columns, vec!["id", "b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_definition_diffs() {
+        let definitions = |items: &[(&str, &str)]| -> BTreeMap<String, String> {
+            items
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let old = definitions(&[("index a", "A"), ("index b", "B"), ("policy p", "P")]);
+        let new = definitions(&[("index a", "A"), ("policy p", "P2"), ("trigger t", "T")]);
+
+        let diffs = calculate_definition_diffs(&old, &new);
+        let summary: Vec<(&str, &str)> = diffs
+            .iter()
+            .map(|d| (d.object.as_str(), d.change_type.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("index b", "removed"),
+                ("policy p", "modified"),
+                ("trigger t", "added")
+            ]
+        );
+        assert_eq!(diffs[1].old_definition.as_deref(), Some("P"));
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "BEGIN\n  RETURN 1;\nEND";
+        let new = "BEGIN\n  PERFORM log();\n  RETURN 2;\nEND";
+
+        assert_eq!(
+            diff_lines(old, new),
+            vec![
+                LineDiff::Same("BEGIN".to_string()),
+                LineDiff::Removed("  RETURN 1;".to_string()),
+                LineDiff::Added("  PERFORM log();".to_string()),
+                LineDiff::Added("  RETURN 2;".to_string()),
+                LineDiff::Same("END".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pair_key_changes() {
+        let old_rows = vec![
+            create_row(&[("id", "5"), ("name", "Alice")]),
+            create_row(&[("id", "6"), ("name", "Bob")]),
+        ];
+        let new_rows = vec![
+            create_row(&[("id", "6"), ("name", "Bob")]),
+            create_row(&[("id", "7"), ("name", "Carol")]),
+            create_row(&[("id", "99"), ("name", "Alice")]),
+        ];
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &pk(&["id"]), DiffScope::Full);
+        assert_eq!(diffs.len(), 3);
+
+        // Without unmatched updates, rows stay removed and added
+        assert_eq!(pair_key_changes(diffs.clone(), 0).len(), 3);
+
+        let diffs = pair_key_changes(diffs, 1);
+        let summary: Vec<(&str, &str)> = diffs
+            .iter()
+            .map(|d| (d.pk_values[0].as_str(), d.change_type.as_str()))
+            .collect();
+        assert_eq!(summary, vec![("7", "added"), ("99", "modified")]);
+        assert_eq!(diffs[1].changed_columns, vec!["id"]);
+        assert_eq!(
+            diffs[1].old_values.as_ref().and_then(|row| row.get("id")),
+            Some(&Value::Int(5))
+        );
+    }
+
+    #[test]
+    fn test_pair_key_changes_ambiguous() {
+        // Unchanged rows with the same values do not get in the way
+        let old_rows = vec![
+            create_row(&[("id", "1"), ("name", "x")]),
+            create_row(&[("id", "2"), ("name", "x")]),
+        ];
+        let new_rows = vec![
+            create_row(&[("id", "2"), ("name", "x")]),
+            create_row(&[("id", "3"), ("name", "x")]),
+        ];
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &pk(&["id"]), DiffScope::Full);
+        assert_eq!(diffs.len(), 2);
+        let diffs = pair_key_changes(diffs, 1);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].change_type, "modified");
+
+        // Two removed rows with the same values cannot be told apart
+        let old_rows = vec![
+            create_row(&[("id", "1"), ("name", "x")]),
+            create_row(&[("id", "2"), ("name", "x")]),
+        ];
+        let new_rows = vec![
+            create_row(&[("id", "3"), ("name", "x")]),
+            create_row(&[("id", "4"), ("name", "x")]),
+        ];
+        let diffs = calculate_all_diffs(&old_rows, &new_rows, &pk(&["id"]), DiffScope::Full);
+        assert_eq!(pair_key_changes(diffs, 2).len(), 4);
+    }
+
+    #[test]
+    fn test_find_parent_rows() {
+        use crate::types::{FetchStrategy, ForeignKey, RowIdentity, TableMeta};
+
+        let items = TableMeta {
+            foreign_keys: vec![ForeignKey {
+                columns: pk(&["order_id"]),
+                referenced_table: "public.orders".to_string(),
+                referenced_columns: pk(&["id"]),
+                on_delete_cascade: true,
+            }],
+            ..TableMeta::new(RowIdentity::none(), Vec::new(), FetchStrategy::Full)
+        };
+        let metadata = TableMetadata::from([("public.items".to_string(), items)]);
+
+        let diff = |table: &str, change_type: &str, row: &[(&str, &str)]| {
+            let row = Some(create_row(row));
+            RowDiff {
+                table: table.to_string(),
+                pk_columns: pk(&["id"]),
+                pk_values: Vec::new(),
+                change_type: change_type.to_string(),
+                old_values: if change_type == "added" {
+                    None
+                } else {
+                    row.clone()
+                },
+                new_values: if change_type == "removed" { None } else { row },
+                changed_columns: Vec::new(),
+                xid: None,
+            }
+        };
+        let diffs = vec![
+            diff("public.items", "removed", &[("id", "1"), ("order_id", "5")]),
+            diff("public.items", "added", &[("id", "2"), ("order_id", "6")]),
+            diff("public.items", "removed", &[("id", "3"), ("order_id", "7")]),
+            diff("public.orders", "removed", &[("id", "5")]),
+            diff("public.orders", "added", &[("id", "6")]),
+        ];
+
+        assert_eq!(
+            find_parent_rows(&diffs, &metadata),
+            vec![
+                Some(RowParent {
+                    index: 3,
+                    cascade: Cascade::Possible
+                }),
+                Some(RowParent {
+                    index: 4,
+                    cascade: Cascade::No
+                }),
+                None,
+                None,
+                None,
+            ]
+        );
+
+        // A cascade is certain only when both deletes share a transaction
+        let with_xid = |mut diff: RowDiff, xid: i64| {
+            diff.xid = Some(xid);
+            diff
+        };
+        let diffs = vec![
+            with_xid(
+                diff("public.items", "removed", &[("id", "1"), ("order_id", "5")]),
+                10,
+            ),
+            with_xid(
+                diff("public.items", "removed", &[("id", "2"), ("order_id", "6")]),
+                11,
+            ),
+            with_xid(diff("public.orders", "removed", &[("id", "5")]), 10),
+            with_xid(diff("public.orders", "removed", &[("id", "6")]), 12),
+        ];
+        let cascades: Vec<Option<Cascade>> = find_parent_rows(&diffs, &metadata)
+            .iter()
+            .map(|parent| parent.map(|p| p.cascade))
+            .collect();
+        assert_eq!(
+            cascades,
+            vec![Some(Cascade::Certain), Some(Cascade::No), None, None]
+        );
+    }
+
+    #[test]
+    fn test_find_parent_rows_cycle() {
+        use crate::types::{FetchStrategy, ForeignKey, RowIdentity, TableMeta};
+
+        // employees.manager_id references employees.id
+        let employees = TableMeta {
+            foreign_keys: vec![ForeignKey {
+                columns: pk(&["manager_id"]),
+                referenced_table: "public.employees".to_string(),
+                referenced_columns: pk(&["id"]),
+                on_delete_cascade: false,
+            }],
+            ..TableMeta::new(RowIdentity::none(), Vec::new(), FetchStrategy::Full)
+        };
+        let metadata = TableMetadata::from([("public.employees".to_string(), employees)]);
+        let diff = |id: &str, manager_id: &str| RowDiff {
+            table: "public.employees".to_string(),
+            pk_columns: pk(&["id"]),
+            pk_values: vec![id.to_string()],
+            change_type: "added".to_string(),
+            old_values: None,
+            new_values: Some(create_row(&[("id", id), ("manager_id", manager_id)])),
+            changed_columns: Vec::new(),
+            xid: None,
+        };
+
+        // 1 manages itself, 2 and 3 manage each other, 4 reports to 3
+        let diffs = vec![
+            diff("1", "1"),
+            diff("2", "3"),
+            diff("3", "2"),
+            diff("4", "3"),
+        ];
+        let parents: Vec<Option<usize>> = find_parent_rows(&diffs, &metadata)
+            .iter()
+            .map(|parent| parent.map(|p| p.index))
+            .collect();
+        assert_eq!(parents, vec![None, None, Some(1), Some(2)]);
+    }
 }
@@ -114,6 +114,7 @@ pub fn print_help() {
     eprintln!("│  {}            Clear history            │", "c".yellow());
     eprintln!("│  {}            Show watching tables     │", "w".yellow());
     eprintln!("│  {}            Reset table selection    │", "r".yellow());
+    eprintln!("│  {}  Watch NAME[:KEY]=SQL     │", "watch-query".yellow());
     eprintln!("│  {}            Show this help           │", "h".yellow());
     eprintln!("│  {}            Quit                     │", "q".yellow());
     eprintln!("{}", "╰─────────────────────────────────────────╯".cyan());
@@ -9,11 +9,7 @@ use crate::types::TableChange;
 pub fn print_change(change: &TableChange, interactive: bool, diff_count: usize) {
     let (icon, ct) = get_change_icon_and_color(change);
 
-    let table_display = if change.schema.is_empty() {
-        change.table.clone()
-    } else {
-        format!("{}.{}", change.schema, change.table)
-    };
+    let table_display = change.target();
 
     let row_suffix = if change.row_count > 1 { "s" } else { "" };
     let diff_hint = if diff_count > 0 && interactive {
@@ -2,9 +2,13 @@
 
 use colored::*;
 
-use super::{extract_table_and_column, format_diff_values, get_change_symbol};
-use crate::constants::display::MAX_INLINE_DIFF_ROWS;
-use crate::types::RowDiff;
+use super::{
+    describe_partial, format_diff_values, format_previous_key, format_row_counts, format_row_key,
+    format_value, get_change_symbol,
+};
+use crate::constants::display::{DEFINITION_CONTEXT_LINES, MAX_INLINE_DIFF_ROWS};
+use crate::diff::{diff_lines, find_parent_rows, Cascade, LineDiff, RowParent};
+use crate::types::{DefinitionDiff, RowDiff, TableMetadata};
 
 /// Display inline diff
 #[allow(dead_code)]
@@ -16,18 +20,16 @@ pub fn print_inline_diff(diffs: &[RowDiff]) {
             break;
         }
 
-        let (table_name, pk_col) = extract_table_and_column(&diff.pk_column);
-
         // Show separator when table changes
-        if !table_name.is_empty() && table_name != current_table {
+        if !diff.table.is_empty() && diff.table != current_table {
             if !current_table.is_empty() {
                 eprintln!();
             }
-            eprintln!("  {}", format!("── {} ──", table_name).dimmed());
-            current_table = table_name;
+            eprintln!("  {}", format!("── {} ──", diff.table).dimmed());
+            current_table = diff.table.clone();
         }
 
-        print_diff_line(diff, &pk_col);
+        print_diff_line(diff);
     }
 
     if diffs.len() > MAX_INLINE_DIFF_ROWS {
@@ -40,29 +42,40 @@ pub fn print_inline_diff(diffs: &[RowDiff]) {
 
 /// Display single diff line
 #[allow(dead_code)]
-fn print_diff_line(diff: &RowDiff, pk_col: &str) {
+fn print_diff_line(diff: &RowDiff) {
     let symbol = get_change_symbol(&diff.change_type);
-    let values = format_diff_values(diff, pk_col);
+    let values = format_diff_values(diff);
+    let key = match format_row_key(diff) {
+        Some((key_cols, key_values)) => format!("{}={}", key_cols, key_values),
+        None => "row".to_string(),
+    };
 
     if !values.is_empty() {
-        eprintln!(
-            "    {} {} {{ {} }}",
-            symbol,
-            format!("{}={}", pk_col, diff.pk_value).cyan(),
-            values.join(", ")
-        );
+        eprintln!("    {} {} {{ {} }}", symbol, key.cyan(), values.join(", "));
     }
 }
 
 /// Display detail view diffs
-pub fn print_detail_diffs(diffs: &[RowDiff]) {
+///
+/// Rows referencing another changed row through a foreign key are shown
+/// nested under it.
+pub fn print_detail_diffs(diffs: &[RowDiff], metadata: &TableMetadata) {
     let mut current_table = String::new();
+    let parents = find_parent_rows(diffs, metadata);
+    let mut children = vec![Vec::new(); diffs.len()];
+    for (i, link) in parents.iter().enumerate() {
+        if let Some(link) = link {
+            children[link.index].push(i);
+        }
+    }
 
-    for d in diffs {
-        let (table_name, pk_col) = extract_table_and_column(&d.pk_column);
+    for (i, d) in diffs.iter().enumerate() {
+        if parents[i].is_some() {
+            continue;
+        }
 
         // Show separator when table changes
-        if !table_name.is_empty() && table_name != current_table {
+        if !d.table.is_empty() && d.table != current_table {
             if !current_table.is_empty() {
                 eprintln!("║");
                 eprintln!(
@@ -70,64 +83,217 @@ pub fn print_detail_diffs(diffs: &[RowDiff]) {
                     "╠───────────────────────────────────────────────────────────╣".dimmed()
                 );
             }
-            eprintln!("║  {}", format!("📋 {}", table_name).cyan().bold());
+            eprintln!("║  {}", format!("📋 {}", d.table).cyan().bold());
             eprintln!(
                 "{}",
                 "╠───────────────────────────────────────────────────────────╣".dimmed()
             );
-            current_table = table_name;
+            current_table = d.table.clone();
+
+            if let Some(partial) = metadata.get(&d.table).and_then(describe_partial) {
+                eprintln!("║  {}", format!("⚠ {}", partial).yellow());
+            }
+
+            // Rows without identity are only counted, never matched up
+            if d.pk_columns.is_empty() {
+                let table_diffs: Vec<&RowDiff> =
+                    diffs.iter().filter(|t| t.table == d.table).collect();
+                eprintln!(
+                    "║  {}",
+                    format!("{} (no row identity)", format_row_counts(&table_diffs)).dimmed()
+                );
+            }
         }
 
-        let sym = get_change_symbol(&d.change_type);
+        eprintln!("║");
+        print_detail_row_key(d, "");
+        print_detail_diff_values(d, "");
+        print_child_rows(diffs, &parents, &children, i, 1);
+    }
+}
+
+/// Display the rows nested under a changed row
+fn print_child_rows(
+    diffs: &[RowDiff],
+    parents: &[Option<RowParent>],
+    children: &[Vec<usize>],
+    parent: usize,
+    depth: usize,
+) {
+    let indent = "  ".repeat(depth);
+    for &i in &children[parent] {
+        let d = &diffs[i];
 
         eprintln!("║");
-        eprintln!(
-            "║  {} {} = {}",
+        print_detail_row_key(d, &format!("{}↳ {} ", indent, d.table.cyan()));
+        let cause = match parents[i].map(|link| link.cascade) {
+            Some(Cascade::Certain) => Some("deleted by cascade from"),
+            Some(Cascade::Possible) => Some("possibly deleted by cascade from"),
+            _ => None,
+        };
+        if let Some(cause) = cause {
+            let cause = format!("{} {}", cause, diffs[parent].table);
+            eprintln!("║      {}{}", indent, cause.magenta());
+        }
+        print_detail_diff_values(d, &indent);
+        print_child_rows(diffs, parents, children, i, depth + 1);
+    }
+}
+
+/// Display the key line of a changed row
+fn print_detail_row_key(d: &RowDiff, prefix: &str) {
+    let sym = get_change_symbol(&d.change_type);
+    let previous_key = format_previous_key(d);
+    match format_row_key(d) {
+        Some((key_cols, key_values)) => eprintln!(
+            "║  {}{} {}={}{}",
+            prefix,
             sym,
-            pk_col.cyan(),
-            d.pk_value.cyan().bold()
+            key_cols.cyan(),
+            key_values.cyan().bold(),
+            previous_key
+                .as_ref()
+                .map(|old| format!(
+                    "  {}",
+                    format!("identity changed {} → {}", old, key_values)
+                        .magenta()
+                        .bold()
+                ))
+                .unwrap_or_default()
+        ),
+        None => eprintln!("║  {}{} {}", prefix, sym, "row".cyan()),
+    }
+}
+
+/// Display catalog object definition changes
+pub fn print_definition_diffs(definitions: &[DefinitionDiff]) {
+    eprintln!("║  {}", "⚙ catalog".cyan().bold());
+    eprintln!(
+        "{}",
+        "╠───────────────────────────────────────────────────────────╣".dimmed()
+    );
+
+    for d in definitions {
+        eprintln!("║");
+        eprintln!(
+            "║  {} {}",
+            get_change_symbol(&d.change_type),
+            d.object.cyan()
         );
 
-        print_detail_diff_values(d, &pk_col);
+        let old = d.old_definition.as_deref().unwrap_or_default();
+        let new = d.new_definition.as_deref().unwrap_or_default();
+        let lines = diff_lines(old, new);
+        // Keep unchanged lines only near a change
+        let near_change = |i: usize| {
+            let start = i.saturating_sub(DEFINITION_CONTEXT_LINES);
+            let end = (i + DEFINITION_CONTEXT_LINES + 1).min(lines.len());
+            lines[start..end]
+                .iter()
+                .any(|l| !matches!(l, LineDiff::Same(_)))
+        };
+
+        let mut skipped = false;
+        for (i, line) in lines.iter().enumerate() {
+            match line {
+                LineDiff::Removed(text) => eprintln!("║      {}", format!("- {}", text).red()),
+                LineDiff::Added(text) => eprintln!("║      {}", format!("+ {}", text).green()),
+                LineDiff::Same(text) if near_change(i) => {
+                    eprintln!("║      {}", format!("  {}", text).dimmed());
+                    skipped = false;
+                }
+                LineDiff::Same(_) => {
+                    if !skipped {
+                        eprintln!("║      {}", "  ...".dimmed());
+                        skipped = true;
+                    }
+                }
+            }
+        }
     }
 }
 
-/// Display detail view values
-fn print_detail_diff_values(diff: &RowDiff, pk_col: &str) {
+/// Display detail view values (nested rows are indented further)
+fn print_detail_diff_values(diff: &RowDiff, indent: &str) {
     match diff.change_type.as_str() {
         "added" => {
             if let Some(nv) = &diff.new_values {
                 for (k, v) in nv {
-                    if k != pk_col {
-                        eprintln!("║      {}: {}", k.dimmed(), v.green());
+                    if !diff.pk_columns.contains(k) {
+                        eprintln!(
+                            "║      {}{}: {}",
+                            indent,
+                            k.dimmed(),
+                            format_value(v).green()
+                        );
                     }
                 }
             }
         }
-        "removed" => {
-            if let Some(ov) = &diff.old_values {
+        "removed" => match &diff.old_values {
+            Some(ov) => {
                 for (k, v) in ov {
-                    if k != pk_col {
-                        eprintln!("║      {}: {}", k.dimmed(), v.red().strikethrough());
+                    if !diff.pk_columns.contains(k) {
+                        eprintln!(
+                            "║      {}{}: {}",
+                            indent,
+                            k.dimmed(),
+                            format_value(v).red().strikethrough()
+                        );
                     }
                 }
             }
-        }
+            None => eprintln!(
+                "║      {}{}",
+                indent,
+                "row data not sent (table has no replica identity)".dimmed()
+            ),
+        },
         "modified" => {
+            if diff.old_values.is_none() && diff.changed_columns.is_empty() {
+                eprintln!(
+                    "║      {}{}",
+                    indent,
+                    "row hash changed; previous values not kept".dimmed()
+                );
+            }
+            let key_changed = format_previous_key(diff).is_some();
             for col in &diff.changed_columns {
+                // A key change is shown on the key line
+                if key_changed && diff.pk_columns.contains(col) {
+                    continue;
+                }
                 let ov = diff
                     .old_values
                     .as_ref()
                     .and_then(|v| v.get(col))
-                    .map(|s| s.as_str())
-                    .unwrap_or("?");
+                    .map(format_value);
                 let nv = diff
                     .new_values
                     .as_ref()
                     .and_then(|v| v.get(col))
-                    .map(|s| s.as_str())
-                    .unwrap_or("?");
-                eprintln!("║      {}: {} → {}", col, ov.white(), nv.yellow());
+                    .map(format_value)
+                    .unwrap_or_else(|| "?".to_string());
+                // Logical decoding only sends the old image with REPLICA IDENTITY FULL
+                match ov {
+                    Some(ov) => {
+                        eprintln!("║      {}{}: {} → {}", indent, col, ov.white(), nv.yellow())
+                    }
+                    None => eprintln!("║      {}{}: {}", indent, col, nv.yellow()),
+                }
+            }
+            // Unchanged columns as context
+            if let Some(nv) = &diff.new_values {
+                for (k, v) in nv {
+                    if !diff.changed_columns.contains(k) && !diff.pk_columns.contains(k) {
+                        eprintln!(
+                            "║      {}{}: {}",
+                            indent,
+                            k.dimmed(),
+                            format_value(v).dimmed()
+                        );
+                    }
+                }
             }
         }
         _ => {}
@@ -2,25 +2,100 @@
 
 use colored::*;
 
-use super::colorize_change_type;
-use super::diff::print_detail_diffs;
-use crate::types::{ChangeHistory, ChangeRecord};
+use super::diff::{print_definition_diffs, print_detail_diffs};
+use super::{colorize_change_type, describe_partial};
+use crate::types::{
+    ChangeHistory, ChangeRecord, FetchStrategy, IdentityStrategy, QuerySpec, RelationKind,
+    RowIdentity, TableMeta, TableMetadata,
+};
 
-/// Display watching tables list
-pub fn print_watching_tables(tables: &[(String, String)], prefix: &str) {
+/// Display watching tables list (watched queries are listed after the tables)
+pub fn print_watching_tables(
+    tables: &[(String, String)],
+    queries: &[QuerySpec],
+    metadata: &TableMetadata,
+    prefix: &str,
+) {
     let suffix = if tables.len() == 1 { "" } else { "s" };
+    let query_count = match queries.len() {
+        0 => String::new(),
+        1 => ", 1 query".to_string(),
+        n => format!(", {} queries", n),
+    };
     eprintln!(
-        "\n{} ({} table{})",
+        "\n{} ({} table{}{})",
         prefix.cyan().bold(),
         tables.len(),
-        suffix
+        suffix,
+        query_count
     );
-    for (i, (schema, table)) in tables.iter().enumerate() {
-        eprintln!("  {} {}.{}", format!("[{}]", i + 1).cyan(), schema, table);
+    let names = tables
+        .iter()
+        .map(|(schema, table)| format!("{}.{}", schema, table))
+        .chain(queries.iter().map(|q| q.name.clone()));
+    for (i, name) in names.enumerate() {
+        let identity = metadata
+            .get(&name)
+            .map(|m| {
+                let mut parts: Vec<String> = describe_kind(m).into_iter().collect();
+                parts.push(describe_identity(&m.identity));
+                parts.extend(describe_strategy(&m.strategy));
+                parts.extend(describe_partial(m));
+                format!("({})", parts.join(", "))
+            })
+            .unwrap_or_default();
+        eprintln!(
+            "  {} {} {}",
+            format!("[{}]", i + 1).cyan(),
+            name,
+            identity.dimmed()
+        );
     }
     eprintln!();
 }
 
+/// Describe the kind of a relation (`None` for plain tables)
+fn describe_kind(meta: &TableMeta) -> Option<String> {
+    match meta.kind {
+        RelationKind::Table => None,
+        RelationKind::Partitioned => Some(format!(
+            "partitioned: {} partition{}",
+            meta.partitions.len(),
+            if meta.partitions.len() == 1 { "" } else { "s" }
+        )),
+        RelationKind::View => Some("view, re-queried".to_string()),
+        RelationKind::MaterializedView => Some("materialized view, re-queried".to_string()),
+        RelationKind::Foreign => Some("foreign table, re-queried".to_string()),
+        RelationKind::Query => Some("query".to_string()),
+    }
+}
+
+/// Describe how rows of a table are identified
+fn describe_identity(identity: &RowIdentity) -> String {
+    let columns = identity.columns.join(", ");
+    match &identity.strategy {
+        IdentityStrategy::PrimaryKey => format!("primary key: {}", columns),
+        IdentityStrategy::UniqueIndex(name) => format!("unique index {}: {}", name, columns),
+        IdentityStrategy::ReplicaIdentity(Some(name)) => {
+            format!("replica identity {}: {}", name, columns)
+        }
+        IdentityStrategy::ReplicaIdentity(None) => "replica identity full".to_string(),
+        IdentityStrategy::QueryKey => format!("key: {}", columns),
+        IdentityStrategy::None => "no row identity".to_string(),
+    }
+}
+
+/// Describe how changed rows of a table are fetched (`None` for full snapshots)
+fn describe_strategy(strategy: &FetchStrategy) -> Option<String> {
+    match strategy {
+        FetchStrategy::Full => None,
+        FetchStrategy::Xmin => Some("incremental".to_string()),
+        FetchStrategy::SinceColumn { column, .. } => Some(format!("since {}", column)),
+        FetchStrategy::Keyset { column, .. } => Some(format!("append-only by {}", column)),
+        FetchStrategy::RowHash => Some("row hashes".to_string()),
+    }
+}
+
 /// Display change history
 pub fn print_history(history: &ChangeHistory) {
     let h = history.lock().unwrap();
@@ -51,24 +126,29 @@ pub fn print_change_line(record: &ChangeRecord, indent: &str) {
         String::new()
     };
 
-    let row_suffix = if c.row_count > 1 { "s" } else { "" };
+    let row_suffix = if c.row_count == 1 { "" } else { "s" };
+    let transaction = c
+        .transaction
+        .map(|xid| format!(" tx {}", xid).dimmed().to_string())
+        .unwrap_or_default();
 
-    let table_display = if c.schema.is_empty() {
-        c.table.clone()
-    } else {
-        format!("{}.{}", c.schema, c.table)
-    };
+    let cycle = c
+        .cycle
+        .map(|n| format!(" cycle {}", n).dimmed().to_string())
+        .unwrap_or_default();
 
     eprintln!(
-        "{}{} [{}] {} {} ({} row{}){}",
+        "{}{} [{}] {} {} ({} row{}){}{}{}",
         indent,
         format!("#{}", c.id).cyan().bold(),
         c.timestamp.dimmed(),
         ct,
-        table_display,
+        c.target(),
         c.row_count,
         row_suffix,
-        diff_hint
+        diff_hint,
+        transaction,
+        cycle
     );
 }
 
@@ -78,7 +158,7 @@ fn print_history_item(record: &ChangeRecord) {
 }
 
 /// Show details
-pub fn show_details(history: &ChangeHistory, id: usize) {
+pub fn show_details(history: &ChangeHistory, metadata: &TableMetadata, id: usize) {
     let record = history
         .lock()
         .unwrap()
@@ -87,7 +167,7 @@ pub fn show_details(history: &ChangeHistory, id: usize) {
         .cloned();
 
     match record {
-        Some(r) => print_detail_view(&r),
+        Some(r) => print_detail_view(&r, metadata),
         None => {
             eprintln!(
                 "{} Change #{} not found. Type 'l' to list all changes.",
@@ -99,12 +179,14 @@ pub fn show_details(history: &ChangeHistory, id: usize) {
 }
 
 /// Display detail view
-fn print_detail_view(record: &ChangeRecord) {
+fn print_detail_view(record: &ChangeRecord, metadata: &TableMetadata) {
     let c = &record.change;
     let ct = match c.change_type.as_str() {
         "INSERT" => c.change_type.green().bold(),
         "UPDATE" => c.change_type.yellow().bold(),
         "DELETE" => c.change_type.red().bold(),
+        "TRUNCATE" => c.change_type.magenta().bold(),
+        "SCHEMA" | "CATALOG" => c.change_type.blue().bold(),
         _ => c.change_type.normal(),
     };
 
@@ -114,12 +196,11 @@ fn print_detail_view(record: &ChangeRecord) {
         "╔══════════════════════════════════════════════════════════╗".cyan()
     );
     eprintln!(
-        "║  {} #{}: {} on {}.{}",
+        "║  {} #{}: {} on {}",
         "Change".cyan().bold(),
         c.id.to_string().cyan(),
         ct,
-        c.schema,
-        c.table
+        c.target()
     );
     eprintln!(
         "║  {}: {}   {}: {} row(s)",
@@ -128,15 +209,45 @@ fn print_detail_view(record: &ChangeRecord) {
         "Affected".dimmed(),
         c.row_count
     );
+    if let Some(xid) = c.transaction {
+        eprintln!("║  {}: {}", "Transaction".dimmed(), xid);
+    }
+    if let Some(cycle) = c.cycle {
+        eprintln!("║  {}: {}", "Cycle".dimmed(), cycle);
+    }
     eprintln!(
         "{}",
         "╠══════════════════════════════════════════════════════════╣".cyan()
     );
 
-    if record.diffs.is_empty() {
-        eprintln!("║  {}", "No detailed diff available.".dimmed());
+    for note in &record.notes {
+        eprintln!("║  {}", note.magenta());
+    }
+
+    if record.diffs.is_empty() && record.definitions.is_empty() {
+        if record.notes.is_empty() {
+            eprintln!("║  {}", "No detailed diff available.".dimmed());
+        }
     } else {
-        print_detail_diffs(&record.diffs);
+        if !record.notes.is_empty() {
+            eprintln!(
+                "{}",
+                "╠───────────────────────────────────────────────────────────╣".dimmed()
+            );
+        }
+        if !record.diffs.is_empty() {
+            print_detail_diffs(&record.diffs, metadata);
+        }
+        if !record.definitions.is_empty() {
+            if !record.diffs.is_empty() {
+                eprintln!("║");
+                eprintln!(
+                    "{}",
+                    "╠───────────────────────────────────────────────────────────╣".dimmed()
+                );
+            }
+            print_definition_diffs(&record.definitions);
+        }
     }
 
     eprintln!(
@@ -20,7 +20,11 @@ pub use messages::{
 use colored::ColoredString;
 use colored::*;
 
-use crate::types::RowDiff;
+use crate::constants::display::{TIMESTAMPTZ_FORMAT, TIMESTAMP_FORMAT};
+use crate::diff::get_pk_value;
+use crate::state::time_display;
+use crate::types::{RowDiff, TableMeta};
+use crate::value::Value;
 
 /// Colorize change type
 pub(crate) fn colorize_change_type(change_type: &str) -> ColoredString {
@@ -28,17 +32,90 @@ pub(crate) fn colorize_change_type(change_type: &str) -> ColoredString {
         "INSERT" => change_type.green(),
         "UPDATE" => change_type.yellow(),
         "DELETE" => change_type.red(),
+        "TRUNCATE" => change_type.magenta(),
+        "SCHEMA" | "CATALOG" => change_type.blue(),
         _ => change_type.normal(),
     }
 }
 
-/// Extract table name and column name from pk_column
-pub(crate) fn extract_table_and_column(pk_column: &str) -> (String, String) {
-    let parts: Vec<&str> = pk_column.rsplitn(2, '.').collect();
-    if parts.len() == 2 {
-        (parts[1].to_string(), parts[0].to_string())
+/// Format primary key columns and values, e.g. `(user_id, role_id)` and `(1, 7)`
+///
+/// Returns `None` for rows without identity.
+pub(crate) fn format_row_key(diff: &RowDiff) -> Option<(String, String)> {
+    if diff.pk_columns.is_empty() {
+        return None;
+    }
+    Some((
+        format_key_list(&diff.pk_columns),
+        format_key_list(&diff.pk_values),
+    ))
+}
+
+/// Format the previous key of a row whose key columns were updated
+pub(crate) fn format_previous_key(diff: &RowDiff) -> Option<String> {
+    if diff.change_type != "modified"
+        || !diff
+            .changed_columns
+            .iter()
+            .any(|c| diff.pk_columns.contains(c))
+    {
+        return None;
+    }
+    let old = diff.old_values.as_ref()?;
+    Some(format_key_list(&get_pk_value(old, &diff.pk_columns)))
+}
+
+/// Summarize added/removed counts, e.g. `2 rows added, 1 row removed`
+pub(crate) fn format_row_counts(diffs: &[&RowDiff]) -> String {
+    let count = |change_type: &str| {
+        diffs
+            .iter()
+            .filter(|d| d.change_type == change_type)
+            .count()
+    };
+    let rows = |n: usize| if n == 1 { "row" } else { "rows" };
+
+    [("added", count("added")), ("removed", count("removed"))]
+        .iter()
+        .filter(|(_, n)| *n > 0)
+        .map(|(label, n)| format!("{} {} {}", n, rows(*n), label))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Describe the tracked window of a partially tracked table
+pub(crate) fn describe_partial(meta: &TableMeta) -> Option<String> {
+    let boundary = meta.boundary.as_ref()?;
+    if meta.identity.is_key() {
+        Some(format!(
+            "partially tracked: rows up to {}={}",
+            format_key_list(&meta.identity.columns),
+            format_key_list(boundary)
+        ))
     } else {
-        (String::new(), pk_column.to_string())
+        Some("partially tracked".to_string())
+    }
+}
+
+/// Format a key list (parenthesized when composite)
+fn format_key_list(items: &[String]) -> String {
+    if items.len() == 1 {
+        items[0].clone()
+    } else {
+        format!("({})", items.join(", "))
+    }
+}
+
+/// Render a column value for display
+///
+/// Empty text and the text `NULL` are quoted so they are not mistaken for
+/// a real NULL.
+pub(crate) fn format_value(value: &Value) -> String {
+    match value {
+        Value::Text(s) if s.is_empty() || s == "NULL" => format!("\"{}\"", s),
+        Value::Timestamp(t) => time_display().format_naive(*t, TIMESTAMP_FORMAT),
+        Value::TimestampTz(t) => time_display().format_instant(*t, TIMESTAMPTZ_FORMAT),
+        other => other.to_string(),
     }
 }
 
@@ -54,15 +131,15 @@ pub(crate) fn get_change_symbol(change_type: &str) -> ColoredString {
 
 /// Format row diff values
 #[allow(dead_code)]
-pub(crate) fn format_diff_values(diff: &RowDiff, pk_col: &str) -> Vec<String> {
+pub(crate) fn format_diff_values(diff: &RowDiff) -> Vec<String> {
     match diff.change_type.as_str() {
         "added" => diff
             .new_values
             .as_ref()
             .map(|nv| {
                 nv.iter()
-                    .filter(|(k, _)| *k != pk_col)
-                    .map(|(k, v)| format!("{}={}", k.dimmed(), v.green()))
+                    .filter(|(k, _)| !diff.pk_columns.contains(k))
+                    .map(|(k, v)| format!("{}={}", k.dimmed(), format_value(v).green()))
                     .collect()
             })
             .unwrap_or_default(),
@@ -71,8 +148,8 @@ pub(crate) fn format_diff_values(diff: &RowDiff, pk_col: &str) -> Vec<String> {
             .as_ref()
             .map(|ov| {
                 ov.iter()
-                    .filter(|(k, _)| *k != pk_col)
-                    .map(|(k, v)| format!("{}={}", k.dimmed(), v.red()))
+                    .filter(|(k, _)| !diff.pk_columns.contains(k))
+                    .map(|(k, v)| format!("{}={}", k.dimmed(), format_value(v).red()))
                     .collect()
             })
             .unwrap_or_default(),
@@ -84,15 +161,17 @@ pub(crate) fn format_diff_values(diff: &RowDiff, pk_col: &str) -> Vec<String> {
                     .old_values
                     .as_ref()
                     .and_then(|v| v.get(col))
-                    .map(|s| s.as_str())
-                    .unwrap_or("?");
+                    .map(format_value);
                 let nv = diff
                     .new_values
                     .as_ref()
                     .and_then(|v| v.get(col))
-                    .map(|s| s.as_str())
-                    .unwrap_or("?");
-                format!("{}: {} → {}", col, ov.white(), nv.yellow())
+                    .map(format_value)
+                    .unwrap_or_else(|| "?".to_string());
+                match ov {
+                    Some(ov) => format!("{}: {} → {}", col, ov.white(), nv.yellow()),
+                    None => format!("{}: {}", col, nv.yellow()),
+                }
             })
             .collect(),
         _ => vec![],
@@ -17,6 +17,30 @@ pub enum TableTraceError {
     #[error("Unknown preset '{0}'. Available: supabase, postgres")]
     UnknownPreset(String),
 
+    /// Unknown change source
+    #[error("Unknown source '{0}'. Available: polling, logical, triggers")]
+    UnknownSource(String),
+
+    /// Unknown event grouping
+    #[error("Unknown grouping '{0}'. Available: cycle, transaction")]
+    UnknownGrouping(String),
+
+    /// Unknown display time zone
+    #[error("Unknown time zone '{0}'. Use 'local', an IANA name (e.g. Asia/Tokyo) or an offset (e.g. +09:00)")]
+    UnknownTimeZone(String),
+
+    /// Logical decoding is not available on the server
+    #[error("Logical decoding requires wal_level=logical (current: {0})")]
+    LogicalDecodingUnavailable(String),
+
+    /// Capture triggers are not installed
+    #[error("Trigger capture is not installed. Run 'tabletrace capture install' first")]
+    CaptureNotInstalled,
+
+    /// Watched query cannot be run
+    #[error("Query '{0}': {1}")]
+    InvalidQuery(String, String),
+
     /// Database name not specified
     #[error("Database name is required. Use --database or --preset")]
     DatabaseRequired,
@@ -6,10 +6,10 @@ use std::sync::atomic::Ordering;
 
 use crate::display::{print_goodbye, print_help, print_history, print_success, show_details};
 use crate::state::{CHANGE_COUNT, SELECTING_TABLES};
-use crate::types::ChangeHistory;
+use crate::types::{ChangeHistory, TableMetadata};
 
 /// Handle user input
-pub fn handle_input(input: &str, history: &ChangeHistory) {
+pub fn handle_input(input: &str, history: &ChangeHistory, metadata: &TableMetadata) {
     let t = input.trim();
     if t.is_empty() {
         return;
@@ -26,7 +26,7 @@ pub fn handle_input(input: &str, history: &ChangeHistory) {
         "r" | "reset" | "reselect" => {
             SELECTING_TABLES.store(true, Ordering::SeqCst);
         }
-        _ => handle_unknown_input(t, history),
+        _ => handle_unknown_input(t, history, metadata),
     }
 }
 
@@ -38,9 +38,9 @@ fn clear_history(history: &ChangeHistory) {
 }
 
 /// Handle unknown input
-fn handle_unknown_input(input: &str, history: &ChangeHistory) {
+fn handle_unknown_input(input: &str, history: &ChangeHistory, metadata: &TableMetadata) {
     if let Ok(num) = input.parse::<usize>() {
-        show_details(history, num);
+        show_details(history, metadata, num);
     } else {
         eprintln!(
             "{} Unknown command '{}'. Type 'h' for help.",
@@ -29,31 +29,38 @@
 //!
 //! # Module Structure
 //!
+//! - `capture`: Opt-in trigger-based capture management
 //! - `cli`: Command line argument parsing
 //! - `constants`: Constant definitions
 //! - `db`: Database operations
+//! - `decoding`: Logical decoding output parser
 //! - `diff`: Diff calculation
 //! - `display`: Display handling (submodule)
 //! - `error`: Error type definitions
 //! - `input`: User input handling
 //! - `state`: Global state management
 //! - `types`: Data type definitions
+//! - `value`: Typed column values
 //! - `watcher`: Watch loop
 
+mod capture;
 mod cli;
 mod constants;
 mod db;
+mod decoding;
 mod diff;
 mod display;
 mod error;
 mod input;
 mod state;
 mod types;
+mod value;
 mod watcher;
 
 use clap::Parser;
 
-use cli::Cli;
+use capture::run_capture;
+use cli::{Cli, Commands};
 use watcher::watch_tables;
 
 #[tokio::main]
@@ -62,15 +69,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let cli = Cli::parse();
 
-    let config = match cli.command.into_watch_config() {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            std::process::exit(1);
+    match cli.command {
+        Commands::Capture { action } => {
+            let config = match action.into_capture_config() {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            run_capture(config).await?;
         }
-    };
+        command => {
+            let config = match command.into_watch_config() {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
 
-    watch_tables(config).await?;
+            watch_tables(config).await?;
+        }
+    }
 
     Ok(())
 }
@@ -2,6 +2,9 @@
 
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicUsize;
+use std::sync::OnceLock;
+
+use crate::types::TimeDisplay;
 
 /// Whether connection has been lost
 pub static CONNECTION_LOST: AtomicBool = AtomicBool::new(false);
@@ -11,3 +14,11 @@ pub static SELECTING_TABLES: AtomicBool = AtomicBool::new(false);
 
 /// Change counter
 pub static CHANGE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Timestamp display settings (set once when watching starts)
+pub static TIME_DISPLAY: OnceLock<TimeDisplay> = OnceLock::new();
+
+/// Get the timestamp display settings
+pub fn time_display() -> &'static TimeDisplay {
+    TIME_DISPLAY.get_or_init(TimeDisplay::default)
+}
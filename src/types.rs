@@ -1,14 +1,19 @@
 //! Data type definitions module
 
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, Utc};
+use indexmap::IndexMap;
 use serde::Serialize;
 use std::collections::HashMap;
+use std::fmt::Write;
 use std::sync::{Arc, Mutex};
 
 use crate::constants::{defaults, presets};
 use crate::error::{Result, TableTraceError};
+use crate::value::Value;
 
-/// Data type representing a single row in a table
-pub type RowData = HashMap<String, String>;
+/// Data type representing a single row in a table (columns in table order)
+pub type RowData = IndexMap<String, Value>;
 
 /// Type for holding change history
 pub type ChangeHistory = Arc<Mutex<Vec<ChangeRecord>>>;
@@ -25,17 +30,303 @@ pub struct TableChange {
     pub schema: String,
     pub change_type: String,
     pub row_count: i64,
+    /// Transaction the changes were made in (when grouped by transaction)
+    pub transaction: Option<i64>,
+    /// Cycle shared by entries split per table
+    pub cycle: Option<usize>,
+}
+
+impl TableChange {
+    /// Qualified name of the changed table, or a summary of what changed
+    pub fn target(&self) -> String {
+        if self.schema.is_empty() {
+            self.table.clone()
+        } else {
+            format!("{}.{}", self.schema, self.table)
+        }
+    }
 }
 
 /// Row-level diff information
 #[derive(Debug, Clone)]
 pub struct RowDiff {
-    pub pk_column: String,
-    pub pk_value: String,
+    /// Qualified table name (`schema.table`)
+    pub table: String,
+    /// Primary key columns, in key order
+    pub pk_columns: Vec<String>,
+    /// Primary key values, matching `pk_columns`
+    pub pk_values: Vec<String>,
     pub change_type: String,
     pub old_values: Option<RowData>,
     pub new_values: Option<RowData>,
     pub changed_columns: Vec<String>,
+    /// Transaction that made the change, when known
+    pub xid: Option<i64>,
+}
+
+/// How rows of a table are matched between snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdentityStrategy {
+    /// Primary key columns
+    PrimaryKey,
+    /// Columns of a unique index on NOT NULL columns (index name)
+    UniqueIndex(String),
+    /// Columns of the `REPLICA IDENTITY USING INDEX` index (index name),
+    /// or all columns with `REPLICA IDENTITY FULL` (`None`)
+    ReplicaIdentity(Option<String>),
+    /// Key columns chosen for a watched query
+    QueryKey,
+    /// No usable row identity
+    None,
+}
+
+/// Row identity used to match rows between snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowIdentity {
+    pub strategy: IdentityStrategy,
+    pub columns: Vec<String>,
+    /// SQL types of the identity columns
+    pub types: Vec<String>,
+}
+
+impl RowIdentity {
+    /// Identity for tables without any usable key
+    pub fn none() -> Self {
+        Self {
+            strategy: IdentityStrategy::None,
+            columns: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+
+    /// Whether rows are ordered by the identity columns (otherwise by row value)
+    pub fn is_key(&self) -> bool {
+        matches!(
+            self.strategy,
+            IdentityStrategy::PrimaryKey
+                | IdentityStrategy::UniqueIndex(_)
+                | IdentityStrategy::ReplicaIdentity(Some(_))
+        )
+    }
+}
+
+/// Column of a watched table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    /// Column number (`pg_attribute.attnum`, or the result position for queries)
+    pub attnum: i16,
+    pub name: String,
+    pub type_oid: u32,
+    /// SQL type name (of the base type, for domains)
+    pub type_name: String,
+}
+
+/// How changed rows of a table are fetched
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum FetchStrategy {
+    /// Re-read all tracked rows
+    #[default]
+    Full,
+    /// Read only rows written since the last cycle (by `xmin`), plus an identity scan for deletes
+    Xmin,
+    /// Read rows whose timestamp column is newer than the last cycle
+    SinceColumn { column: String, column_type: String },
+    /// Read rows whose key column is past the last seen value (insert-only tables)
+    Keyset { column: String, column_type: String },
+    /// Keep only row hashes and read full rows whose hash changed
+    RowHash,
+}
+
+impl FetchStrategy {
+    /// Which changes the fetched rows are authoritative for
+    pub fn diff_scope(&self) -> DiffScope {
+        match self {
+            Self::Full | Self::Xmin | Self::RowHash => DiffScope::Full,
+            Self::SinceColumn { .. } | Self::Keyset { .. } => DiffScope::Slice,
+        }
+    }
+}
+
+/// Extent of the rows compared against a snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffScope {
+    /// All tracked rows: missing rows were deleted
+    Full,
+    /// Only recently changed rows: missing rows are unchanged
+    Slice,
+}
+
+/// Per-table fetch strategy options
+#[derive(Debug, Clone, Default)]
+pub struct FetchOptions {
+    /// Use the xmin strategy on tables with a key
+    pub incremental: bool,
+    /// Use the row hash strategy on tables with a key
+    pub row_hash: bool,
+    /// `(table, column)` pairs for the since-column strategy
+    pub since_columns: Vec<(String, String)>,
+    /// `(table, column)` pairs for the append-only keyset strategy
+    pub append_only: Vec<(String, String)>,
+}
+
+/// Kind of a watched relation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RelationKind {
+    #[default]
+    Table,
+    /// Partitioned table, shown as one table over all its partitions
+    Partitioned,
+    View,
+    MaterializedView,
+    Foreign,
+    /// Result of a watched query
+    Query,
+}
+
+impl RelationKind {
+    /// Map a `pg_class.relkind` code
+    pub fn from_relkind(relkind: &str) -> Self {
+        match relkind {
+            "p" => Self::Partitioned,
+            "v" => Self::View,
+            "m" => Self::MaterializedView,
+            "f" => Self::Foreign,
+            _ => Self::Table,
+        }
+    }
+
+    /// Whether changes show up in tuple counters (otherwise the relation is re-queried)
+    pub fn has_counters(&self) -> bool {
+        matches!(self, Self::Table | Self::Partitioned)
+    }
+}
+
+/// Read-only query watched as a virtual table
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuerySpec {
+    pub name: String,
+    /// Key columns (empty: rows are compared as a multiset)
+    pub key: Vec<String>,
+    pub sql: String,
+}
+
+impl QuerySpec {
+    /// Parse `NAME=SQL` or `NAME:KEY[,KEY...]=SQL`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || {
+            TableTraceError::config(format!(
+                "Invalid query '{}': expected NAME=SQL or NAME:KEY=SQL",
+                spec
+            ))
+        };
+        let (head, sql) = spec.split_once('=').ok_or_else(invalid)?;
+        let (name, key) = match head.split_once(':') {
+            Some((name, key)) => (name, key.split(',').map(|c| c.trim().to_string()).collect()),
+            None => (head, Vec::new()),
+        };
+        let name = name.trim();
+        let sql = sql.trim().trim_end_matches(';').trim_end();
+
+        // Names must not collide with `schema.table` keys
+        if name.is_empty() || name.contains('.') || sql.is_empty() {
+            return Err(invalid());
+        }
+        if key.iter().any(String::is_empty) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            key,
+            sql: sql.to_string(),
+        })
+    }
+}
+
+/// Metadata discovered for a watched table
+#[derive(Debug, Clone)]
+pub struct TableMeta {
+    pub kind: RelationKind,
+    /// Leaf partitions (`schema.table`) of a partitioned table
+    pub partitions: Vec<String>,
+    pub identity: RowIdentity,
+    /// Columns in table definition order (empty if they could not be read)
+    pub columns: Vec<Column>,
+    pub strategy: FetchStrategy,
+    /// Order key of the last tracked row once the table exceeded the row cap
+    pub boundary: Option<Vec<String>>,
+    /// Order key of the last row in the current snapshot
+    pub last_key: Option<Vec<String>>,
+    /// Watermark of the current snapshot for incremental fetches
+    /// (transaction id or strategy column value)
+    pub watermark: Option<String>,
+    /// Oldest transaction running at the previous column strategy fetch
+    pub xmin_horizon: Option<String>,
+    /// Row hashes by identity values (row hash strategy)
+    pub fingerprints: HashMap<Vec<String>, String>,
+    /// Hash of the whole relation (relations without tuple counters)
+    pub content_hash: Option<String>,
+    /// Hash of the column names and types, to detect schema changes
+    pub column_fingerprint: Option<String>,
+    /// Foreign keys of the table to other tables
+    pub foreign_keys: Vec<ForeignKey>,
+}
+
+impl TableMeta {
+    /// Create metadata for a table with the given row identity and columns
+    pub fn new(identity: RowIdentity, columns: Vec<Column>, strategy: FetchStrategy) -> Self {
+        Self {
+            kind: RelationKind::Table,
+            partitions: Vec::new(),
+            identity,
+            columns,
+            strategy,
+            boundary: None,
+            last_key: None,
+            watermark: None,
+            xmin_horizon: None,
+            fingerprints: HashMap::new(),
+            content_hash: None,
+            column_fingerprint: None,
+            foreign_keys: Vec::new(),
+        }
+    }
+
+    /// Forget the tracked window, watermarks and row hashes, so that the
+    /// table is fetched again from scratch
+    pub fn reset_tracking(&mut self) {
+        self.boundary = None;
+        self.last_key = None;
+        self.watermark = None;
+        self.xmin_horizon = None;
+        self.fingerprints.clear();
+    }
+}
+
+/// Foreign key from a table to a referenced table
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    /// Referenced table (`schema.table`)
+    pub referenced_table: String,
+    /// Referenced columns, matching `columns`
+    pub referenced_columns: Vec<String>,
+    /// Whether rows are deleted with their referenced row (`ON DELETE CASCADE`)
+    pub on_delete_cascade: bool,
+}
+
+/// Type for holding table metadata (keyed by `schema.table`)
+pub type TableMetadata = HashMap<String, TableMeta>;
+
+/// Change to the definition of a catalog object (function, trigger, policy, ...)
+#[derive(Debug, Clone)]
+pub struct DefinitionDiff {
+    /// Object description, e.g. `trigger audit on public.users`
+    pub object: String,
+    /// "added", "removed" or "modified"
+    pub change_type: String,
+    pub old_definition: Option<String>,
+    pub new_definition: Option<String>,
 }
 
 /// Change record (event + diff)
@@ -43,6 +334,10 @@ pub struct RowDiff {
 pub struct ChangeRecord {
     pub change: TableChange,
     pub diffs: Vec<RowDiff>,
+    /// Changes that have no row diff (e.g. truncated tables)
+    pub notes: Vec<String>,
+    /// Catalog object definition changes
+    pub definitions: Vec<DefinitionDiff>,
 }
 
 /// Table statistics (from pg_stat_user_tables)
@@ -51,6 +346,15 @@ pub struct TableStats {
     pub n_tup_ins: i64,
     pub n_tup_upd: i64,
     pub n_tup_del: i64,
+    pub n_live_tup: i64,
+    /// Storage files of the table and its partitions (replaced by TRUNCATE and rewrites)
+    pub filenodes: Option<String>,
+    /// Oldest transaction running when the statistics were read
+    /// (rows with an older `xmin` existed at that point)
+    pub horizon: String,
+    /// Database statistics reset time and server start time
+    /// (all counters restart from zero when either changes)
+    pub epoch: String,
 }
 
 /// Connection configuration
@@ -149,6 +453,118 @@ impl Default for ConnectionConfig {
     }
 }
 
+/// Where row changes are read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChangeSource {
+    /// Poll `pg_stat_user_tables` counters and diff table snapshots
+    #[default]
+    Polling,
+    /// Stream row changes from a temporary logical replication slot
+    Logical,
+    /// Receive row changes from capture triggers via LISTEN/NOTIFY
+    Triggers,
+}
+
+impl ChangeSource {
+    /// Parse change source from its CLI name
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "polling" | "stats" => Ok(Self::Polling),
+            "logical" | "wal" => Ok(Self::Logical),
+            "triggers" | "trigger" => Ok(Self::Triggers),
+            _ => Err(TableTraceError::UnknownSource(name.to_string())),
+        }
+    }
+
+    /// Whether this source needs full table snapshots to compute diffs
+    pub fn needs_snapshots(&self) -> bool {
+        matches!(self, Self::Polling)
+    }
+}
+
+/// How detected changes are grouped into history entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EventGrouping {
+    /// One entry per polling cycle
+    #[default]
+    Cycle,
+    /// One entry per originating transaction, in commit order
+    Transaction,
+}
+
+impl EventGrouping {
+    /// Parse grouping from its CLI name
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "cycle" => Ok(Self::Cycle),
+            "transaction" | "tx" => Ok(Self::Transaction),
+            _ => Err(TableTraceError::UnknownGrouping(name.to_string())),
+        }
+    }
+}
+
+/// Time zone timestamps are displayed in
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DisplayTimeZone {
+    /// System local time
+    #[default]
+    Local,
+    /// IANA time zone, e.g. `Asia/Tokyo` or `UTC`
+    Named(chrono_tz::Tz),
+    /// Fixed UTC offset, e.g. `+09:00`
+    Fixed(FixedOffset),
+}
+
+impl DisplayTimeZone {
+    /// Parse time zone from its CLI name
+    pub fn from_name(name: &str) -> Result<Self> {
+        if name.eq_ignore_ascii_case("local") {
+            return Ok(Self::Local);
+        }
+        if let Ok(tz) = name.parse::<chrono_tz::Tz>() {
+            return Ok(Self::Named(tz));
+        }
+        name.parse::<FixedOffset>()
+            .map(Self::Fixed)
+            .map_err(|_| TableTraceError::UnknownTimeZone(name.to_string()))
+    }
+}
+
+/// How timestamps are rendered
+#[derive(Debug, Clone, Default)]
+pub struct TimeDisplay {
+    pub zone: DisplayTimeZone,
+    /// strftime format used for all timestamps instead of the defaults
+    pub format: Option<String>,
+}
+
+impl TimeDisplay {
+    /// Format an instant in the display time zone
+    pub fn format_instant(&self, time: DateTime<Utc>, default_format: &str) -> String {
+        let format = self.format.as_deref().unwrap_or(default_format);
+        match self.zone {
+            DisplayTimeZone::Local => time.with_timezone(&Local).format(format).to_string(),
+            DisplayTimeZone::Named(tz) => time.with_timezone(&tz).format(format).to_string(),
+            DisplayTimeZone::Fixed(offset) => {
+                time.with_timezone(&offset).format(format).to_string()
+            }
+        }
+    }
+
+    /// Format a timestamp without time zone (never converted)
+    ///
+    /// A format with zone specifiers (`%z`, `%Z`) cannot render such a
+    /// timestamp, so the default format is used for it instead.
+    pub fn format_naive(&self, time: NaiveDateTime, default_format: &str) -> String {
+        let format = self.format.as_deref().unwrap_or(default_format);
+        let mut text = String::new();
+        match write!(text, "{}", time.format(format)) {
+            Ok(()) => text,
+            Err(_) => time.format(default_format).to_string(),
+        }
+    }
+}
+
 /// Watch configuration
 #[derive(Debug, Clone)]
 pub struct WatchConfig {
@@ -156,6 +572,18 @@ pub struct WatchConfig {
     pub schema: String,
     pub interval: u64,
     pub interactive: bool,
+    pub source: ChangeSource,
+    /// Maximum rows tracked per table (0 = unlimited)
+    pub max_rows: usize,
+    pub fetch: FetchOptions,
+    pub time: TimeDisplay,
+    /// Queries watched as virtual tables
+    pub queries: Vec<QuerySpec>,
+    /// Watch catalog objects (functions, triggers, policies, ...) of the schema
+    pub catalog: bool,
+    pub grouping: EventGrouping,
+    /// Record one history entry per changed table
+    pub per_table: bool,
 }
 
 impl WatchConfig {
@@ -166,6 +594,14 @@ impl WatchConfig {
             schema: schema.into(),
             interval: defaults::POLLING_INTERVAL_MS,
             interactive: true,
+            source: ChangeSource::default(),
+            max_rows: defaults::MAX_ROWS,
+            fetch: FetchOptions::default(),
+            time: TimeDisplay::default(),
+            queries: Vec::new(),
+            catalog: false,
+            grouping: EventGrouping::default(),
+            per_table: false,
         }
     }
 
@@ -181,6 +617,78 @@ impl WatchConfig {
         self
     }
 
+    /// Set change source
+    pub fn with_source(mut self, source: ChangeSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Set maximum rows tracked per table
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    /// Set incremental fetching
+    pub fn with_incremental(mut self, incremental: bool) -> Self {
+        self.fetch.incremental = incremental;
+        self
+    }
+
+    /// Set row hash fetching
+    pub fn with_row_hash(mut self, row_hash: bool) -> Self {
+        self.fetch.row_hash = row_hash;
+        self
+    }
+
+    /// Set `(table, column)` pairs fetched by timestamp column
+    pub fn with_since_columns(mut self, since_columns: Vec<(String, String)>) -> Self {
+        self.fetch.since_columns = since_columns;
+        self
+    }
+
+    /// Set `(table, column)` pairs fetched as append-only tables
+    pub fn with_append_only(mut self, append_only: Vec<(String, String)>) -> Self {
+        self.fetch.append_only = append_only;
+        self
+    }
+
+    /// Set the time zone timestamps are displayed in
+    pub fn with_timezone(mut self, zone: DisplayTimeZone) -> Self {
+        self.time.zone = zone;
+        self
+    }
+
+    /// Set the strftime format for timestamps
+    pub fn with_time_format(mut self, format: Option<String>) -> Self {
+        self.time.format = format;
+        self
+    }
+
+    /// Set queries watched as virtual tables
+    pub fn with_queries(mut self, queries: Vec<QuerySpec>) -> Self {
+        self.queries = queries;
+        self
+    }
+
+    /// Set catalog object watching
+    pub fn with_catalog(mut self, catalog: bool) -> Self {
+        self.catalog = catalog;
+        self
+    }
+
+    /// Set how changes are grouped into history entries
+    pub fn with_grouping(mut self, grouping: EventGrouping) -> Self {
+        self.grouping = grouping;
+        self
+    }
+
+    /// Set one history entry per changed table
+    pub fn with_per_table(mut self, per_table: bool) -> Self {
+        self.per_table = per_table;
+        self
+    }
+
     /// Validate configuration
     pub fn validate(&self) -> Result<()> {
         self.connection.validate()?;
@@ -189,6 +697,26 @@ impl WatchConfig {
                 "Polling interval must be greater than 0",
             ));
         }
+        for (i, query) in self.queries.iter().enumerate() {
+            if self.queries[..i].iter().any(|q| q.name == query.name) {
+                return Err(TableTraceError::config(format!(
+                    "Duplicate query name '{}'",
+                    query.name
+                )));
+            }
+        }
+        if let Some(format) = &self.time.format {
+            // Render a sample: unknown specifiers only fail when written
+            let sample = DateTime::<Utc>::UNIX_EPOCH.fixed_offset();
+            if StrftimeItems::new(format).any(|item| item == Item::Error)
+                || write!(String::new(), "{}", sample.format(format)).is_err()
+            {
+                return Err(TableTraceError::config(format!(
+                    "Invalid --time-format '{}'",
+                    format
+                )));
+            }
+        }
         Ok(())
     }
 }
@@ -200,6 +728,112 @@ impl Default for WatchConfig {
             schema: defaults::SCHEMA.to_string(),
             interval: defaults::POLLING_INTERVAL_MS,
             interactive: true,
+            source: ChangeSource::default(),
+            max_rows: defaults::MAX_ROWS,
+            fetch: FetchOptions::default(),
+            time: TimeDisplay::default(),
+            queries: Vec::new(),
+            catalog: false,
+            grouping: EventGrouping::default(),
+            per_table: false,
+        }
+    }
+}
+
+/// Capture trigger management action
+#[derive(Debug, Clone)]
+pub enum CaptureAction {
+    /// Install capture triggers on tables in the schema
+    Install { schema: String },
+    /// Remove all capture triggers and the tabletrace schema
+    Uninstall,
+}
+
+/// Capture trigger management configuration
+#[derive(Debug, Clone)]
+pub struct CaptureConfig {
+    pub connection: ConnectionConfig,
+    pub action: CaptureAction,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_spec_parse() {
+        let spec =
+            QuerySpec::parse("jobs:status=SELECT status, count(*) FROM jobs WHERE x = 1;").unwrap();
+        assert_eq!(spec.name, "jobs");
+        assert_eq!(spec.key, vec!["status"]);
+        assert_eq!(spec.sql, "SELECT status, count(*) FROM jobs WHERE x = 1");
+
+        let spec = QuerySpec::parse(" pairs:a, b = SELECT 1 AS a, 2 AS b").unwrap();
+        assert_eq!(spec.name, "pairs");
+        assert_eq!(spec.key, vec!["a", "b"]);
+
+        assert!(QuerySpec::parse("plain=SELECT 1").unwrap().key.is_empty());
+        assert!(QuerySpec::parse("SELECT 1").is_err());
+        assert!(QuerySpec::parse("public.x=SELECT 1").is_err());
+        assert!(QuerySpec::parse("x:=SELECT 1").is_err());
+        assert!(QuerySpec::parse("x=").is_err());
+    }
+
+    #[test]
+    fn test_relation_kind() {
+        assert_eq!(RelationKind::from_relkind("r"), RelationKind::Table);
+        assert_eq!(RelationKind::from_relkind("p"), RelationKind::Partitioned);
+        assert_eq!(RelationKind::from_relkind("v"), RelationKind::View);
+        assert_eq!(
+            RelationKind::from_relkind("m"),
+            RelationKind::MaterializedView
+        );
+        assert_eq!(RelationKind::from_relkind("f"), RelationKind::Foreign);
+
+        // Only tables and partitioned tables have tuple counters
+        assert!(RelationKind::Table.has_counters());
+        assert!(RelationKind::Partitioned.has_counters());
+        for kind in [
+            RelationKind::View,
+            RelationKind::MaterializedView,
+            RelationKind::Foreign,
+            RelationKind::Query,
+        ] {
+            assert!(!kind.has_counters());
         }
     }
+
+    #[test]
+    fn test_time_format_zone_on_naive_timestamp() {
+        let time =
+            NaiveDateTime::parse_from_str("2026-01-02 03:04:05", "%Y-%m-%d %H:%M:%S").unwrap();
+        let display = TimeDisplay {
+            zone: DisplayTimeZone::Fixed(FixedOffset::east_opt(9 * 3600).unwrap()),
+            format: Some("%Y-%m-%d %H:%M:%S %z".to_string()),
+        };
+
+        // No zone to write: falls back to the default format
+        assert_eq!(
+            display.format_naive(time, "%Y-%m-%d %H:%M:%S"),
+            "2026-01-02 03:04:05"
+        );
+        assert_eq!(
+            display.format_instant(time.and_utc(), "%H:%M"),
+            "2026-01-02 12:04:05 +0900"
+        );
+
+        let config = WatchConfig::new(
+            ConnectionConfig::new("localhost", 5432, "db", "u", "p"),
+            "public",
+        );
+        assert!(config
+            .clone()
+            .with_time_format(Some("%Y-%m-%d %H:%M:%S %z".to_string()))
+            .validate()
+            .is_ok());
+        assert!(config
+            .with_time_format(Some("%Y-%m-%d %Q".to_string()))
+            .validate()
+            .is_err());
+    }
 }
@@ -0,0 +1,429 @@
+//! Typed column value module
+//!
+//! Column values are kept typed from the database (or decoded change
+//! stream) up to the display layer, so that a real SQL NULL is never
+//! confused with the text `"NULL"` and numbers compare as numbers.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Single column value
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    /// Arbitrary precision number, kept as its exact text
+    Numeric(String),
+    Float(f64),
+    Text(String),
+    Json(serde_json::Value),
+    Uuid(uuid::Uuid),
+    Timestamp(NaiveDateTime),
+    TimestampTz(DateTime<Utc>),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    /// Value of a type that could not be decoded
+    Unknown,
+}
+
+/// Equality as a change check: NaN equals NaN, and -0 differs from 0
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) | (Value::Unknown, Value::Unknown) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Numeric(a), Value::Numeric(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => {
+                (a.is_nan() && b.is_nan()) || a.to_bits() == b.to_bits()
+            }
+            (Value::Text(a), Value::Text(b)) => a == b,
+            (Value::Json(a), Value::Json(b)) => a == b,
+            (Value::Uuid(a), Value::Uuid(b)) => a == b,
+            (Value::Timestamp(a), Value::Timestamp(b)) => a == b,
+            (Value::TimestampTz(a), Value::TimestampTz(b)) => a == b,
+            (Value::Date(a), Value::Date(b)) => a == b,
+            (Value::Time(a), Value::Time(b)) => a == b,
+            (Value::Bytes(a), Value::Bytes(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    /// Parse a value from its PostgreSQL text form, given the SQL type name
+    ///
+    /// Falls back to `Text` when the type is not known or the text does not
+    /// parse as that type.
+    pub fn from_text(type_name: &str, text: &str) -> Self {
+        let base = strip_typmod(type_name);
+        if let Some(element) = base.strip_suffix("[]") {
+            return parse_array(text)
+                .map(|items| {
+                    Value::Array(
+                        items
+                            .into_iter()
+                            .map(|item| item.map_or(Value::Null, |t| Value::from_text(element, &t)))
+                            .collect(),
+                    )
+                })
+                .unwrap_or_else(|| Value::Text(text.to_string()));
+        }
+
+        let parsed = match base.as_str() {
+            "boolean" => match text {
+                "t" | "true" => Some(Value::Bool(true)),
+                "f" | "false" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            "smallint" | "integer" | "bigint" | "oid" => text.parse().ok().map(Value::Int),
+            "numeric" => Some(Value::Numeric(text.to_string())),
+            "real" | "double precision" => text.parse().ok().map(Value::Float),
+            "json" | "jsonb" => serde_json::from_str(text).ok().map(Value::Json),
+            "uuid" => text.parse().ok().map(Value::Uuid),
+            "timestamp without time zone" => {
+                NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+                    .ok()
+                    .map(Value::Timestamp)
+            }
+            "timestamp with time zone" => DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z")
+                .ok()
+                .map(|t| Value::TimestampTz(t.with_timezone(&Utc))),
+            "date" => NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .ok()
+                .map(Value::Date),
+            "time without time zone" => NaiveTime::parse_from_str(text, "%H:%M:%S%.f")
+                .ok()
+                .map(Value::Time),
+            "bytea" => parse_hex_bytes(text).map(Value::Bytes),
+            _ => None,
+        };
+        parsed.unwrap_or_else(|| Value::Text(text.to_string()))
+    }
+
+    /// Convert a JSON value (from `to_jsonb(row)`) into a column value
+    pub fn from_json(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(*b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Value::Int(i),
+                None => Value::Numeric(n.to_string()),
+            },
+            serde_json::Value::String(s) => Value::Text(s.clone()),
+            other => Value::Json(other.clone()),
+        }
+    }
+}
+
+impl Value {
+    /// Compare values in PostgreSQL sort order (`ASC NULLS LAST`)
+    ///
+    /// Text compares byte-wise, as under the `C` collation. Values of
+    /// different kinds, which never share a column, compare by kind.
+    pub fn sort_cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Null, _) => Ordering::Greater,
+            (_, Value::Null) => Ordering::Less,
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Numeric(a), Value::Numeric(b)) => compare_numeric(a, b),
+            // NaN sorts after every number, and -0 equals 0
+            (Value::Float(a), Value::Float(b)) => match (a.is_nan(), b.is_nan()) {
+                (false, false) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+                (a, b) => a.cmp(&b),
+            },
+            (Value::Text(a), Value::Text(b)) => a.cmp(b),
+            (Value::Json(a), Value::Json(b)) => a.to_string().cmp(&b.to_string()),
+            (Value::Uuid(a), Value::Uuid(b)) => a.cmp(b),
+            (Value::Timestamp(a), Value::Timestamp(b)) => a.cmp(b),
+            (Value::TimestampTz(a), Value::TimestampTz(b)) => a.cmp(b),
+            (Value::Date(a), Value::Date(b)) => a.cmp(b),
+            (Value::Time(a), Value::Time(b)) => a.cmp(b),
+            (Value::Bytes(a), Value::Bytes(b)) => a.cmp(b),
+            (Value::Array(a), Value::Array(b)) => a
+                .iter()
+                .zip(b)
+                .map(|(x, y)| x.sort_cmp(y))
+                .find(|order| order.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            _ => self.kind().cmp(&other.kind()),
+        }
+    }
+
+    /// Rank of the value kind, to order values of different kinds
+    fn kind(&self) -> u8 {
+        match self {
+            Value::Bool(_) => 0,
+            Value::Int(_) => 1,
+            Value::Numeric(_) => 2,
+            Value::Float(_) => 3,
+            Value::Text(_) => 4,
+            Value::Json(_) => 5,
+            Value::Uuid(_) => 6,
+            Value::Timestamp(_) => 7,
+            Value::TimestampTz(_) => 8,
+            Value::Date(_) => 9,
+            Value::Time(_) => 10,
+            Value::Bytes(_) => 11,
+            Value::Array(_) => 12,
+            Value::Unknown => 13,
+            Value::Null => 14,
+        }
+    }
+}
+
+/// Compare two exact decimal texts (`NaN` sorts after every number)
+fn compare_numeric(a: &str, b: &str) -> Ordering {
+    match (parse_decimal(a), parse_decimal(b)) {
+        (Some(x), Some(y)) => {
+            let order = match (x.0, y.0) {
+                (false, true) => return Ordering::Greater,
+                (true, false) => return Ordering::Less,
+                _ => (x.1.len(), &x.1, &x.2).cmp(&(y.1.len(), &y.1, &y.2)),
+            };
+            if x.0 {
+                order.reverse()
+            } else {
+                order
+            }
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+/// Split a decimal text into its sign, integer digits and fraction digits,
+/// without leading or trailing zeros (zero is never negative)
+fn parse_decimal(text: &str) -> Option<(bool, String, String)> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    if int.is_empty() && frac.is_empty()
+        || !int.bytes().chain(frac.bytes()).all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let int = int.trim_start_matches('0').to_string();
+    let frac = frac.trim_end_matches('0').to_string();
+    let negative = negative && !(int.is_empty() && frac.is_empty());
+    Some((negative, int, frac))
+}
+
+/// Exact text form, used for row keys and fingerprints
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Numeric(s) | Value::Text(s) => write!(f, "{}", s),
+            Value::Float(n) => write!(f, "{}", n),
+            Value::Json(j) => write!(f, "{}", j),
+            Value::Uuid(u) => write!(f, "{}", u),
+            Value::Timestamp(t) => write!(f, "{}", t.format("%Y-%m-%d %H:%M:%S%.f")),
+            Value::TimestampTz(t) => write!(f, "{}", t.format("%Y-%m-%d %H:%M:%S%.f%:z")),
+            Value::Date(d) => write!(f, "{}", d.format("%Y-%m-%d")),
+            Value::Time(t) => write!(f, "{}", t.format("%H:%M:%S%.f")),
+            Value::Bytes(bytes) => {
+                write!(f, "\\x")?;
+                bytes.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            }
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(|v| v.to_string()).collect();
+                write!(f, "{{{}}}", items.join(","))
+            }
+            Value::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// Remove type modifiers, e.g. `character varying(20)` → `character varying`
+fn strip_typmod(type_name: &str) -> String {
+    let mut base = String::new();
+    let mut depth = 0;
+    for c in type_name.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if depth == 0 => base.push(c),
+            _ => {}
+        }
+    }
+    base.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parse a one-dimensional array literal, e.g. `{a,"b c",NULL}`
+fn parse_array(text: &str) -> Option<Vec<Option<String>>> {
+    let inner = text.strip_prefix('{')?.strip_suffix('}')?;
+    if inner.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let mut items = Vec::new();
+    let mut chars = inner.chars().peekable();
+    loop {
+        let item = if chars.peek() == Some(&'"') {
+            chars.next();
+            let mut item = String::new();
+            loop {
+                match chars.next()? {
+                    '\\' => item.push(chars.next()?),
+                    '"' => break,
+                    c => item.push(c),
+                }
+            }
+            Some(item)
+        } else {
+            let mut item = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ',' {
+                    break;
+                }
+                if c == '{' || c == '"' {
+                    // Nested arrays are kept as text
+                    return None;
+                }
+                item.push(c);
+                chars.next();
+            }
+            (item != "NULL").then_some(item)
+        };
+        items.push(item);
+
+        match chars.next() {
+            Some(',') => continue,
+            None => return Some(items),
+            Some(_) => return None,
+        }
+    }
+}
+
+/// Parse `bytea` hex output, e.g. `\x0102`
+fn parse_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    let hex = text.strip_prefix("\\x")?;
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_text_scalars() {
+        assert_eq!(Value::from_text("integer", "42"), Value::Int(42));
+        assert_eq!(Value::from_text("boolean", "true"), Value::Bool(true));
+        assert_eq!(
+            Value::from_text("numeric(10,2)", "1.50"),
+            Value::Numeric("1.50".to_string())
+        );
+        assert_eq!(
+            Value::from_text("character varying(20)", "NULL"),
+            Value::Text("NULL".to_string())
+        );
+        assert_eq!(
+            Value::from_text("bytea", "\\x01ff"),
+            Value::Bytes(vec![0x01, 0xff])
+        );
+        assert_eq!(
+            Value::from_text("timestamp(3) without time zone", "2025-01-02 03:04:05.123")
+                .to_string(),
+            "2025-01-02 03:04:05.123"
+        );
+        assert_eq!(
+            Value::from_text("timestamp with time zone", "2025-01-02 03:04:05+09").to_string(),
+            "2025-01-01 18:04:05+00:00"
+        );
+    }
+
+    #[test]
+    fn test_from_text_fallback() {
+        assert_eq!(
+            Value::from_text("integer", "abc"),
+            Value::Text("abc".to_string())
+        );
+        assert_eq!(
+            Value::from_text("point", "(1,2)"),
+            Value::Text("(1,2)".to_string())
+        );
+        assert_eq!(
+            Value::from_text("public.hstore", "\"a\"=>\"1\""),
+            Value::Text("\"a\"=>\"1\"".to_string())
+        );
+        assert_eq!(Value::from_text("oid", "16384"), Value::Int(16384));
+    }
+
+    #[test]
+    fn test_from_text_array() {
+        assert_eq!(
+            Value::from_text("integer[]", "{1,NULL,3}"),
+            Value::Array(vec![Value::Int(1), Value::Null, Value::Int(3)])
+        );
+        assert_eq!(
+            Value::from_text("text[]", r#"{a,"b,c","NULL","q\"t"}"#),
+            Value::Array(vec![
+                Value::Text("a".to_string()),
+                Value::Text("b,c".to_string()),
+                Value::Text("NULL".to_string()),
+                Value::Text("q\"t".to_string()),
+            ])
+        );
+        assert_eq!(
+            Value::from_text("integer[]", "{{1,2},{3,4}}"),
+            Value::Text("{{1,2},{3,4}}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_from_json() {
+        assert_eq!(Value::from_json(&serde_json::json!(null)), Value::Null);
+        assert_eq!(Value::from_json(&serde_json::json!(7)), Value::Int(7));
+        assert_eq!(
+            Value::from_json(&serde_json::json!("NULL")),
+            Value::Text("NULL".to_string())
+        );
+        assert_eq!(
+            Value::from_json(&serde_json::json!({"a": 1})),
+            Value::Json(serde_json::json!({"a": 1}))
+        );
+    }
+
+    #[test]
+    fn test_sort_cmp() {
+        let numeric = |s: &str| Value::Numeric(s.to_string());
+        let text = |s: &str| Value::Text(s.to_string());
+
+        assert_eq!(Value::Int(9).sort_cmp(&Value::Int(10)), Ordering::Less);
+        assert_eq!(numeric("9.5").sort_cmp(&numeric("10")), Ordering::Less);
+        assert_eq!(numeric("-10").sort_cmp(&numeric("-9.99")), Ordering::Less);
+        assert_eq!(numeric("1.50").sort_cmp(&numeric("1.5")), Ordering::Equal);
+        assert_eq!(numeric("-0").sort_cmp(&numeric("0.00")), Ordering::Equal);
+        assert_eq!(
+            numeric("NaN").sort_cmp(&numeric("1000000000")),
+            Ordering::Greater
+        );
+        assert_eq!(
+            Value::Float(f64::NAN).sort_cmp(&Value::Float(f64::INFINITY)),
+            Ordering::Greater
+        );
+        // Byte order, as under the C collation
+        assert_eq!(text("B").sort_cmp(&text("a")), Ordering::Less);
+        assert_eq!(text("10").sort_cmp(&text("9")), Ordering::Less);
+        assert_eq!(Value::Null.sort_cmp(&Value::Int(1)), Ordering::Greater);
+    }
+}
@@ -0,0 +1,61 @@
+//! Catalog object watching
+//!
+//! Functions, triggers, row level security policies, indexes, constraints
+//! and grants of the watched schema are fingerprinted each cycle. Their
+//! definitions are only fetched and compared when the fingerprint changes.
+
+use std::collections::BTreeMap;
+
+use crate::db::{get_catalog_hash, get_catalog_objects};
+use crate::diff::calculate_definition_diffs;
+
+use super::changes::CycleResult;
+
+/// Catalog objects of the watched schema and their last known definitions
+pub struct CatalogWatcher {
+    schema: String,
+    hash: String,
+    objects: BTreeMap<String, String>,
+}
+
+impl CatalogWatcher {
+    /// Take the current catalog objects as the baseline
+    pub async fn start(
+        client: &tokio_postgres::Client,
+        schema: &str,
+    ) -> Result<Self, tokio_postgres::Error> {
+        Ok(Self {
+            schema: schema.to_string(),
+            hash: get_catalog_hash(client, schema).await?,
+            objects: get_catalog_objects(client, schema).await?,
+        })
+    }
+
+    /// Number of watched catalog objects
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Collect definition changes since the previous call
+    pub async fn collect_changes(&mut self, client: &tokio_postgres::Client) -> CycleResult {
+        let mut result = CycleResult::default();
+
+        let Ok(hash) = get_catalog_hash(client, &self.schema).await else {
+            return result;
+        };
+        if hash == self.hash {
+            return result;
+        }
+        let Ok(objects) = get_catalog_objects(client, &self.schema).await else {
+            return result;
+        };
+        self.hash = hash;
+
+        result.definitions = calculate_definition_diffs(&self.objects, &objects);
+        if !result.definitions.is_empty() {
+            result.change_types.insert("CATALOG".to_string());
+        }
+        self.objects = objects;
+        result
+    }
+}
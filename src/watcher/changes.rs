@@ -1,57 +1,413 @@
 //! Change collection and event creation
 
-use chrono::Local;
+use chrono::{DateTime, Utc};
 use std::collections::{HashMap, HashSet};
+use tracing::warn;
 
-use crate::constants::display::MAX_HISTORY_SIZE;
-use crate::db::{fetch_all_rows, get_primary_key};
-use crate::diff::calculate_all_diffs;
-use crate::types::{ChangeHistory, ChangeRecord, RowDiff, TableChange, TableSnapshots, TableStats};
+use crate::constants::display::{EVENT_TIME_FORMAT, MAX_HISTORY_SIZE};
+use crate::db::{get_content_hash, has_rows_before};
+use crate::diff::{calculate_all_diffs, pair_key_changes};
+use crate::state::time_display;
+use crate::types::{
+    ChangeHistory, ChangeRecord, DefinitionDiff, DiffScope, FetchStrategy, RowDiff, TableChange,
+    TableMeta, TableMetadata, TableSnapshots, TableStats,
+};
 
-use super::stats::detect_changes;
+use super::hashing::fetch_hash_diffs;
+use super::incremental::update_snapshot;
+use super::schema::ColumnChange;
+use super::snapshot::fetch_tracked_rows;
+use super::stats::{
+    counters_reset, detect_changes, explain_discrepancies, truncate_suspected,
+    unmatched_key_updates,
+};
 
 /// Cycle change result
+#[derive(Default)]
 pub struct CycleResult {
     pub diffs: Vec<RowDiff>,
     pub tables: Vec<String>,
     pub change_types: HashSet<String>,
     pub total_rows: i64,
+    /// Truncated tables and the number of rows lost (when known)
+    pub truncated: Vec<(String, Option<i64>)>,
+    /// Tables whose columns changed, with the column-level changes
+    pub schema_changes: Vec<(String, Vec<String>)>,
+    /// Catalog object definition changes
+    pub definitions: Vec<DefinitionDiff>,
+    /// Tables whose statistics counters were reset (diffed by rows instead)
+    pub stats_reset: Vec<String>,
+    /// Counted changes that left no row diff, with their likely cause
+    pub discrepancies: Vec<(String, String)>,
+    /// Transactions of the row diffs in commit order, with their commit time when known
+    pub transactions: Vec<(i64, Option<DateTime<Utc>>)>,
+    /// Change types and row counts of each table
+    pub table_counts: HashMap<String, TableCounts>,
+    /// Whether the order of the transactions is not their commit order
+    /// (changes of unknown transactions, or transactions ordered by start)
+    pub unordered: bool,
+}
+
+/// Change types and row count of one table in a cycle
+#[derive(Default, Clone)]
+pub struct TableCounts {
+    pub change_types: HashSet<String>,
+    pub rows: i64,
+}
+
+impl CycleResult {
+    /// Whether anything changed in the cycle
+    pub fn has_changes(&self) -> bool {
+        !self.diffs.is_empty()
+            || !self.truncated.is_empty()
+            || !self.schema_changes.is_empty()
+            || !self.definitions.is_empty()
+            || !self.discrepancies.is_empty()
+    }
+
+    /// Record a truncated table (partitions add up on their partitioned table)
+    pub fn add_truncate(&mut self, full_key: String, rows_lost: Option<i64>) {
+        self.count(&full_key, "TRUNCATE", rows_lost.unwrap_or(0));
+        match self.truncated.iter_mut().find(|(key, _)| *key == full_key) {
+            Some((_, lost)) => {
+                *lost = match (*lost, rows_lost) {
+                    (None, None) => None,
+                    (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+                }
+            }
+            None => self.truncated.push((full_key.clone(), rows_lost)),
+        }
+        self.push_table(full_key, Vec::new());
+    }
+
+    /// Record the column changes of a table
+    pub fn add_schema_change(&mut self, full_key: String, changes: &[ColumnChange]) {
+        self.push_schema_change(full_key, changes.iter().map(|c| c.to_string()).collect());
+    }
+
+    /// Record already formatted column changes of a table
+    fn push_schema_change(&mut self, full_key: String, changes: Vec<String>) {
+        self.count(&full_key, "SCHEMA", 0);
+        self.schema_changes.push((full_key.clone(), changes));
+        self.push_table(full_key, Vec::new());
+    }
+
+    /// Count rows changed in a table
+    pub fn count(&mut self, full_key: &str, change_type: &str, rows: i64) {
+        self.total_rows += rows;
+        self.change_types.insert(change_type.to_string());
+        let counts = self.table_counts.entry(full_key.to_string()).or_default();
+        counts.rows += rows;
+        counts.change_types.insert(change_type.to_string());
+    }
+
+    /// Notes on changes without row diffs
+    pub fn notes(&self) -> Vec<String> {
+        let schema = self.schema_changes.iter().flat_map(|(full_key, changes)| {
+            changes
+                .iter()
+                .map(move |change| format!("⚙ {}: {}", full_key, change))
+        });
+        let truncated = self
+            .truncated
+            .iter()
+            .map(|(full_key, rows_lost)| match rows_lost {
+                Some(n) => format!(
+                    "✂ {} truncated: {} row{} lost",
+                    full_key,
+                    n,
+                    if *n == 1 { "" } else { "s" }
+                ),
+                None => format!("✂ {} truncated", full_key),
+            });
+        let reset = self
+            .stats_reset
+            .iter()
+            .filter(|full_key| self.tables.contains(full_key))
+            .map(|full_key| {
+                format!(
+                    "↺ {}: statistics were reset, changes found by comparing rows",
+                    full_key
+                )
+            });
+        let discrepancies = self
+            .discrepancies
+            .iter()
+            .map(|(full_key, cause)| format!("≠ {}: {}", full_key, cause));
+        let order = match self.transactions.len() {
+            _ if !self.unordered => None,
+            0 => Some(
+                "⇅ transaction unknown: these changes may have happened before or between the transactions listed before them"
+                    .to_string(),
+            ),
+            1 => Some(
+                "⇅ commit order unknown (track_commit_timestamp is off): transactions are listed in start order"
+                    .to_string(),
+            ),
+            _ => None,
+        };
+        schema
+            .chain(truncated)
+            .chain(reset)
+            .chain(discrepancies)
+            .chain(order)
+            .collect()
+    }
+
+    /// Count changes from row diffs (when tuple counters cannot be used)
+    pub fn count_diffs(&mut self, diffs: &[RowDiff]) {
+        for diff in diffs {
+            self.count(&diff.table, diff_change_type(&diff.change_type), 1);
+        }
+    }
+
+    /// Record the diffs of a changed table
+    pub fn push_table(&mut self, full_key: String, diffs: Vec<RowDiff>) {
+        self.diffs.extend(diffs);
+        if !self.tables.contains(&full_key) {
+            self.tables.push(full_key);
+        }
+    }
+
+    /// Merge the changes of another result into this one
+    pub fn merge(&mut self, other: CycleResult) {
+        self.total_rows += other.total_rows;
+        self.change_types.extend(other.change_types);
+        for full_key in other.tables {
+            self.push_table(full_key, Vec::new());
+        }
+        self.diffs.extend(other.diffs);
+        self.truncated.extend(other.truncated);
+        self.schema_changes.extend(other.schema_changes);
+        self.definitions.extend(other.definitions);
+        self.stats_reset.extend(other.stats_reset);
+        self.discrepancies.extend(other.discrepancies);
+        self.unordered |= other.unordered;
+        for transaction in other.transactions {
+            self.add_transaction(transaction.0, transaction.1);
+        }
+        for (full_key, counts) in other.table_counts {
+            let own = self.table_counts.entry(full_key).or_default();
+            own.rows += counts.rows;
+            own.change_types.extend(counts.change_types);
+        }
+    }
+
+    /// Record a transaction seen in the cycle (the first time it is seen)
+    pub fn add_transaction(&mut self, xid: i64, committed_at: Option<DateTime<Utc>>) {
+        match self.transactions.iter_mut().find(|(id, _)| *id == xid) {
+            Some((_, at)) => *at = at.or(committed_at),
+            None => self.transactions.push((xid, committed_at)),
+        }
+    }
+
+    /// Split into one result per transaction, in the order of `transactions`
+    ///
+    /// Changes of unknown transactions (rows removed under polling, schema
+    /// and catalog changes, truncates) are kept together in a last result,
+    /// marked unordered when there are transactions they could precede.
+    pub fn split_by_transaction(self) -> Vec<CycleResult> {
+        let mut rest = CycleResult {
+            stats_reset: self.stats_reset.clone(),
+            ..Default::default()
+        };
+        for (full_key, rows_lost) in self.truncated {
+            rest.add_truncate(full_key, rows_lost);
+        }
+        for (full_key, changes) in self.schema_changes {
+            rest.push_schema_change(full_key, changes);
+        }
+        for (full_key, cause) in self.discrepancies {
+            if let Some(counts) = self.table_counts.get(&full_key) {
+                for change_type in &counts.change_types {
+                    rest.count(&full_key, change_type, 0);
+                }
+            }
+            rest.push_table(full_key.clone(), Vec::new());
+            rest.discrepancies.push((full_key, cause));
+        }
+        if !self.definitions.is_empty() {
+            rest.change_types.insert("CATALOG".to_string());
+        }
+        rest.definitions = self.definitions;
+
+        let mut groups: Vec<CycleResult> = self
+            .transactions
+            .iter()
+            .map(|&(xid, committed_at)| CycleResult {
+                transactions: vec![(xid, committed_at)],
+                stats_reset: self.stats_reset.clone(),
+                unordered: self.unordered,
+                ..Default::default()
+            })
+            .collect();
+        for diff in self.diffs {
+            let group = match diff
+                .xid
+                .and_then(|xid| self.transactions.iter().position(|(id, _)| *id == xid))
+            {
+                Some(i) => &mut groups[i],
+                None => &mut rest,
+            };
+            group.count_diffs(std::slice::from_ref(&diff));
+            group.push_table(diff.table.clone(), vec![diff]);
+        }
+
+        rest.unordered = groups.iter().any(|group| group.has_changes());
+        groups
+            .into_iter()
+            .chain(std::iter::once(rest))
+            .filter(|group| group.has_changes())
+            .collect()
+    }
+
+    /// Split into one result per table, in the order tables changed
+    ///
+    /// Catalog changes, which belong to no table, are kept in a last result.
+    pub fn split_by_table(self) -> Vec<CycleResult> {
+        let mut groups: Vec<CycleResult> = self
+            .tables
+            .iter()
+            .map(|full_key| {
+                let counts = self.table_counts.get(full_key).cloned().unwrap_or_default();
+                CycleResult {
+                    tables: vec![full_key.clone()],
+                    change_types: counts.change_types.clone(),
+                    total_rows: counts.rows,
+                    stats_reset: self
+                        .stats_reset
+                        .iter()
+                        .filter(|key| *key == full_key)
+                        .cloned()
+                        .collect(),
+                    transactions: self.transactions.clone(),
+                    table_counts: HashMap::from([(full_key.clone(), counts)]),
+                    unordered: self.unordered,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        let position = |full_key: &String| self.tables.iter().position(|key| key == full_key);
+
+        for diff in self.diffs {
+            if let Some(i) = position(&diff.table) {
+                groups[i].diffs.push(diff);
+            }
+        }
+        for truncated in self.truncated {
+            if let Some(i) = position(&truncated.0) {
+                groups[i].truncated.push(truncated);
+            }
+        }
+        for schema_change in self.schema_changes {
+            if let Some(i) = position(&schema_change.0) {
+                groups[i].schema_changes.push(schema_change);
+            }
+        }
+        for discrepancy in self.discrepancies {
+            if let Some(i) = position(&discrepancy.0) {
+                groups[i].discrepancies.push(discrepancy);
+            }
+        }
+        // Keep only the transactions each table's rows were changed in
+        for group in &mut groups {
+            group
+                .transactions
+                .retain(|(xid, _)| group.diffs.iter().any(|diff| diff.xid == Some(*xid)));
+        }
+        if !self.definitions.is_empty() {
+            groups.push(CycleResult {
+                change_types: HashSet::from(["CATALOG".to_string()]),
+                definitions: self.definitions,
+                ..Default::default()
+            });
+        }
+
+        groups
+            .into_iter()
+            .filter(|group| group.has_changes())
+            .collect()
+    }
 }
 
 /// Collect changes during cycle
+///
+/// Tables in `diffed` were already diffed in this cycle and are skipped.
 pub async fn collect_cycle_changes(
     final_stats: &HashMap<(String, String), TableStats>,
     prev_stats: &HashMap<(String, String), TableStats>,
+    diffed: &[String],
     snapshots: &TableSnapshots,
+    metadata: &mut TableMetadata,
+    max_rows: usize,
     client: &tokio_postgres::Client,
 ) -> CycleResult {
-    let mut result = CycleResult {
-        diffs: Vec::new(),
-        tables: Vec::new(),
-        change_types: HashSet::new(),
-        total_rows: 0,
-    };
+    let mut result = CycleResult::default();
 
     for ((schema, table), stats) in final_stats {
         let key = (schema.clone(), table.clone());
         let full_key = format!("{}.{}", schema, table);
+        if diffed.contains(&full_key) {
+            continue;
+        }
 
         if let Some(prev) = prev_stats.get(&key) {
-            let detected = detect_changes(stats, prev);
+            let Some(meta) = metadata.get_mut(&full_key) else {
+                continue;
+            };
+            // Counters that restarted cannot be compared: diff the rows instead
+            let reset = counters_reset(stats, prev);
+            let detected = if reset {
+                result.stats_reset.push(full_key.clone());
+                Vec::new()
+            } else {
+                detect_changes(stats, prev)
+            };
 
-            if !detected.is_empty() {
-                let diffs =
-                    calculate_table_diffs(client, schema, table, &full_key, snapshots).await;
+            // TRUNCATE does not move the tuple counters but replaces the storage
+            let truncated = if truncate_suspected(stats, prev) {
+                reset_truncated(client, schema, table, meta, prev, snapshots).await
+            } else {
+                None
+            };
+            if let Some(rows_lost) = truncated {
+                if rows_lost > 0 {
+                    result.add_truncate(full_key.clone(), Some(rows_lost));
+                }
+            }
 
-                result.diffs.extend(diffs);
+            if !detected.is_empty() || truncated.is_some() || reset {
+                let mut diffs =
+                    calculate_table_diffs(client, schema, table, meta, max_rows, snapshots).await;
+                if meta.identity.is_key() {
+                    let limit = unmatched_key_updates(&detected, &diffs);
+                    diffs = pair_key_changes(diffs, limit);
+                }
 
-                for (change_type, count) in &detected {
-                    result.total_rows += count;
-                    result.change_types.insert(change_type.to_string());
+                // Rows are counted from the diffs; counted changes without one are explained
+                result.count_diffs(&diffs);
+                if !reset && truncated.is_none() {
+                    let fully_tracked =
+                        meta.boundary.is_none() && meta.strategy.diff_scope() == DiffScope::Full;
+                    let causes = explain_discrepancies(
+                        &detected,
+                        &diffs,
+                        meta.identity.is_key(),
+                        fully_tracked,
+                    );
+                    if diffs.is_empty() && !causes.is_empty() {
+                        // Shown with the counted change types but no rows
+                        for (change_type, _) in &detected {
+                            result.count(&full_key, change_type, 0);
+                        }
+                    }
+                    for cause in causes {
+                        result.discrepancies.push((full_key.clone(), cause));
+                    }
                 }
 
-                if !result.tables.contains(&full_key) {
-                    result.tables.push(full_key);
+                if !diffs.is_empty() || !detected.is_empty() {
+                    result.push_table(full_key, diffs);
                 }
             }
         }
@@ -60,67 +416,215 @@ pub async fn collect_cycle_changes(
     result
 }
 
-/// Calculate table diffs
-async fn calculate_table_diffs(
+/// Check whether a table whose storage was replaced has been truncated
+///
+/// Rows surviving a rewrite (VACUUM FULL, CLUSTER) keep their `xmin`; when
+/// none is older than the previous statistics, the table was truncated. Its
+/// snapshot and tracking state are then dropped so that it is fetched again
+/// from scratch. Returns the number of rows lost. When the check fails, the
+/// table is diffed as usual.
+async fn reset_truncated(
     client: &tokio_postgres::Client,
     schema: &str,
     table: &str,
-    full_key: &str,
+    meta: &mut TableMeta,
+    prev: &TableStats,
     snapshots: &TableSnapshots,
-) -> Vec<RowDiff> {
-    let new_rows = fetch_all_rows(client, schema, table)
-        .await
-        .unwrap_or_default();
-    let old_rows = snapshots
+) -> Option<i64> {
+    match has_rows_before(client, schema, table, &prev.horizon).await {
+        Ok(false) => {}
+        Ok(true) => return None,
+        Err(e) => {
+            warn!("Could not check {}.{} for TRUNCATE: {}", schema, table, e);
+            return None;
+        }
+    }
+
+    let snapshot_rows = snapshots
         .lock()
         .unwrap()
-        .get(full_key)
-        .cloned()
-        .unwrap_or_default();
-    let pk_col = get_primary_key(client, schema, table)
-        .await
-        .unwrap_or_else(|| "id".to_string());
+        .remove(&format!("{}.{}", schema, table))
+        .map_or(0, |rows| rows.len());
+    Some(reset_tracking(meta, snapshot_rows, prev.n_live_tup))
+}
+
+/// Clear the tracking state of a truncated table
+///
+/// Returns the number of rows lost: the live tuple count, or the tracked
+/// rows (snapshot or row hashes) when more were seen than counted.
+fn reset_tracking(meta: &mut TableMeta, snapshot_rows: usize, n_live_tup: i64) -> i64 {
+    let tracked = snapshot_rows.max(meta.fingerprints.len());
+    meta.reset_tracking();
+
+    n_live_tup.max(tracked as i64)
+}
+
+/// Re-query relations without tuple counters and diff those whose content changed
+pub async fn collect_requery_changes(
+    tables: &[(String, String)],
+    snapshots: &TableSnapshots,
+    metadata: &mut TableMetadata,
+    max_rows: usize,
+    client: &tokio_postgres::Client,
+) -> CycleResult {
+    let mut result = CycleResult::default();
+
+    for (schema, table) in tables {
+        let full_key = format!("{}.{}", schema, table);
+        let Some(meta) = metadata.get_mut(&full_key) else {
+            continue;
+        };
+        if meta.kind.has_counters() {
+            continue;
+        }
+        let Ok(hash) = get_content_hash(client, schema, table).await else {
+            continue;
+        };
+        if !record_content_hash(meta, hash) {
+            continue;
+        }
 
-    let mut diffs = calculate_all_diffs(&old_rows, &new_rows, &pk_col);
+        let diffs = calculate_table_diffs(client, schema, table, meta, max_rows, snapshots).await;
+        if !diffs.is_empty() {
+            result.count_diffs(&diffs);
+            result.push_table(full_key, diffs);
+        }
+    }
+
+    result
+}
+
+/// Record the content hash of a re-queried relation
+///
+/// Returns whether it differs from the previous one, in which case the
+/// relation is fetched again and diffed.
+fn record_content_hash(meta: &mut TableMeta, hash: String) -> bool {
+    if meta.content_hash.as_ref() == Some(&hash) {
+        return false;
+    }
+    meta.content_hash = Some(hash);
+    true
+}
+
+/// Map a row diff type to its change type
+fn diff_change_type(diff_type: &str) -> &'static str {
+    match diff_type {
+        "added" => "INSERT",
+        "removed" => "DELETE",
+        _ => "UPDATE",
+    }
+}
+
+/// Calculate table diffs
+pub(super) async fn calculate_table_diffs(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    meta: &mut TableMeta,
+    max_rows: usize,
+    snapshots: &TableSnapshots,
+) -> Vec<RowDiff> {
+    let full_key = format!("{}.{}", schema, table);
+    let mut diffs = if meta.strategy == FetchStrategy::RowHash {
+        fetch_hash_diffs(client, schema, table, meta, max_rows)
+            .await
+            .unwrap_or_default()
+    } else {
+        diff_snapshot(client, schema, table, &full_key, meta, max_rows, snapshots).await
+    };
 
     // Tag with table name
     for diff in &mut diffs {
-        diff.pk_column = format!("{}.{}", full_key, diff.pk_column);
+        diff.table = full_key.clone();
     }
 
-    // Update snapshot
+    diffs
+}
+
+/// Fetch tracked rows, diff them against the snapshot and update it
+async fn diff_snapshot(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    full_key: &str,
+    meta: &mut TableMeta,
+    max_rows: usize,
+    snapshots: &TableSnapshots,
+) -> Vec<RowDiff> {
+    let previous = snapshots.lock().unwrap().get(full_key).cloned();
+    let Ok(new_rows) =
+        fetch_tracked_rows(client, schema, table, meta, max_rows, previous.as_deref()).await
+    else {
+        return Vec::new();
+    };
+    let old_rows = previous.unwrap_or_default();
+    let pk_cols = &meta.identity.columns;
+    let diffs = calculate_all_diffs(&old_rows, &new_rows, pk_cols, meta.strategy.diff_scope());
+
+    let mut snapshot = update_snapshot(&meta.strategy, old_rows, new_rows, pk_cols);
+    // Rows past a lowered boundary leave the tracked window
+    if meta.boundary.is_some() && matches!(meta.strategy, FetchStrategy::Full | FetchStrategy::Xmin)
+    {
+        snapshot.truncate(max_rows);
+    }
     snapshots
         .lock()
         .unwrap()
-        .insert(full_key.to_string(), new_rows);
+        .insert(full_key.to_string(), snapshot);
 
     diffs
 }
 
 /// Create change event
-pub fn create_change_event(
-    id: usize,
-    tables: &[String],
-    change_types: &HashSet<String>,
-    total_rows: i64,
-) -> TableChange {
-    let mut types: Vec<String> = change_types.iter().cloned().collect();
+///
+/// `cycle` links the entries a cycle was split into.
+pub fn create_change_event(id: usize, result: &CycleResult, cycle: Option<usize>) -> TableChange {
+    let mut types: Vec<String> = result.change_types.iter().cloned().collect();
     types.sort();
     let change_type_str = types.join("+");
 
-    let table_str = if tables.len() == 1 {
-        tables[0].clone()
-    } else {
-        format!("{} tables", tables.len())
+    let objects = result.definitions.len();
+    let (schema, table_str) = match (result.tables.as_slice(), objects) {
+        // Queries are named without a schema
+        ([table], 0) => match table.split_once('.') {
+            Some((schema, table)) => (schema.to_string(), table.to_string()),
+            None => (String::new(), table.clone()),
+        },
+        (tables, n) => (String::new(), summarize_targets(tables.len(), n, result)),
+    };
+
+    // A single transaction is shown at its commit time when known
+    let (transaction, committed_at) = match result.transactions.as_slice() {
+        [(xid, committed_at)] => (Some(*xid), *committed_at),
+        _ => (None, None),
     };
 
     TableChange {
         id,
-        timestamp: Local::now().format("%H:%M:%S").to_string(),
-        schema: String::new(),
+        timestamp: time_display()
+            .format_instant(committed_at.unwrap_or_else(Utc::now), EVENT_TIME_FORMAT),
+        schema,
         table: table_str,
         change_type: change_type_str,
-        row_count: total_rows,
+        row_count: result.total_rows,
+        transaction,
+        cycle,
+    }
+}
+
+/// Describe the changed tables and catalog objects of an event
+fn summarize_targets(tables: usize, objects: usize, result: &CycleResult) -> String {
+    match (tables, objects) {
+        (0, 1) => result.definitions[0].object.clone(),
+        (tables, 0) => format!("{} tables", tables),
+        (0, n) => format!("{} catalog objects", n),
+        (tables, n) => format!(
+            "{} table{}, {} catalog object{}",
+            tables,
+            if tables == 1 { "" } else { "s" },
+            n,
+            if n == 1 { "" } else { "s" }
+        ),
     }
 }
 
@@ -128,8 +632,242 @@ pub fn create_change_event(
 #[allow(dead_code)]
 pub fn add_to_history(history: &ChangeHistory, change: TableChange, diffs: Vec<RowDiff>) {
     let mut h = history.lock().unwrap();
-    h.push(ChangeRecord { change, diffs });
+    h.push(ChangeRecord {
+        change,
+        diffs,
+        notes: Vec::new(),
+        definitions: Vec::new(),
+    });
     if h.len() > MAX_HISTORY_SIZE {
         h.remove(0);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(table: &str, id: &str, change_type: &str, xid: Option<i64>) -> RowDiff {
+        RowDiff {
+            table: table.to_string(),
+            pk_columns: vec!["id".to_string()],
+            pk_values: vec![id.to_string()],
+            change_type: change_type.to_string(),
+            old_values: None,
+            new_values: None,
+            changed_columns: Vec::new(),
+            xid,
+        }
+    }
+
+    fn cycle(diffs: Vec<RowDiff>, transactions: &[i64]) -> CycleResult {
+        let mut result = CycleResult::default();
+        for xid in transactions {
+            result.add_transaction(*xid, None);
+        }
+        for diff in diffs {
+            result.count_diffs(std::slice::from_ref(&diff));
+            result.push_table(diff.table.clone(), vec![diff]);
+        }
+        result
+    }
+
+    fn ids(result: &CycleResult) -> Vec<&str> {
+        result
+            .diffs
+            .iter()
+            .map(|d| d.pk_values[0].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_by_transaction() {
+        let mut result = cycle(
+            vec![
+                diff("public.users", "1", "modified", Some(20)),
+                diff("public.users", "2", "removed", None),
+                diff("public.orders", "3", "added", Some(10)),
+                diff("public.users", "4", "added", Some(20)),
+            ],
+            &[10, 20],
+        );
+        result.add_truncate("public.logs".to_string(), Some(5));
+
+        let groups = result.split_by_transaction();
+        assert_eq!(groups.len(), 3);
+
+        // Transactions in order, changes of unknown transactions last
+        assert_eq!(groups[0].transactions, vec![(10, None)]);
+        assert_eq!(ids(&groups[0]), vec!["3"]);
+        assert_eq!(groups[0].tables, vec!["public.orders"]);
+        assert_eq!(groups[1].transactions, vec![(20, None)]);
+        assert_eq!(ids(&groups[1]), vec!["1", "4"]);
+        assert_eq!(groups[1].total_rows, 2);
+
+        let rest = &groups[2];
+        assert!(rest.transactions.is_empty());
+        assert_eq!(ids(rest), vec!["2"]);
+        assert_eq!(rest.tables, vec!["public.logs", "public.users"]);
+        assert_eq!(rest.truncated, vec![("public.logs".to_string(), Some(5))]);
+        assert!(rest.unordered);
+        assert!(rest
+            .notes()
+            .iter()
+            .any(|n| n.starts_with("⇅ transaction unknown")));
+        assert!(!groups[0].unordered && groups[0].notes().is_empty());
+    }
+
+    #[test]
+    fn test_split_by_transaction_without_transactions() {
+        let result = cycle(vec![diff("public.users", "1", "removed", None)], &[]);
+        let groups = result.split_by_transaction();
+
+        assert_eq!(groups.len(), 1);
+        assert!(!groups[0].unordered);
+        assert!(groups[0].notes().is_empty());
+    }
+
+    #[test]
+    fn test_split_by_transaction_in_start_order() {
+        let mut result = cycle(
+            vec![
+                diff("public.users", "1", "added", Some(10)),
+                diff("public.users", "2", "added", Some(20)),
+            ],
+            &[10, 20],
+        );
+        result.unordered = true;
+
+        let groups = result.split_by_transaction();
+        assert_eq!(groups.len(), 2);
+        for group in &groups {
+            assert!(group.notes()[0].starts_with("⇅ commit order unknown"));
+        }
+    }
+
+    #[test]
+    fn test_split_by_table() {
+        let mut result = cycle(
+            vec![
+                diff("public.orders", "1", "added", Some(10)),
+                diff("public.users", "2", "removed", None),
+                diff("public.orders", "3", "modified", Some(20)),
+                diff("public.users", "4", "modified", Some(20)),
+            ],
+            &[10, 20, 30],
+        );
+        result.add_truncate("public.logs".to_string(), None);
+        result.stats_reset.push("public.users".to_string());
+        result.definitions.push(DefinitionDiff {
+            object: "function public.f()".to_string(),
+            change_type: "added".to_string(),
+            old_definition: None,
+            new_definition: Some("f".to_string()),
+        });
+
+        let groups = result.split_by_table();
+        assert_eq!(groups.len(), 4);
+
+        // Tables in the order they changed, with their own counts
+        let orders = &groups[0];
+        assert_eq!(orders.tables, vec!["public.orders"]);
+        assert_eq!(ids(orders), vec!["1", "3"]);
+        assert_eq!(orders.total_rows, 2);
+        assert_eq!(
+            orders.change_types,
+            HashSet::from(["INSERT".to_string(), "UPDATE".to_string()])
+        );
+        assert_eq!(orders.transactions, vec![(10, None), (20, None)]);
+        assert!(orders.stats_reset.is_empty());
+
+        // Rows of unknown transactions do not pull in the other transactions
+        let users = &groups[1];
+        assert_eq!(ids(users), vec!["2", "4"]);
+        assert_eq!(users.transactions, vec![(20, None)]);
+        assert_eq!(users.stats_reset, vec!["public.users"]);
+
+        let logs = &groups[2];
+        assert_eq!(logs.truncated, vec![("public.logs".to_string(), None)]);
+        assert!(logs.transactions.is_empty());
+
+        // Catalog changes come last
+        assert_eq!(groups[3].definitions.len(), 1);
+        assert!(groups[3].tables.is_empty());
+    }
+
+    #[test]
+    fn test_split_by_table_events_share_cycle() {
+        let result = cycle(
+            vec![
+                diff("public.orders", "1", "added", Some(10)),
+                diff("public.users", "2", "added", Some(10)),
+            ],
+            &[10],
+        );
+
+        let events: Vec<TableChange> = result
+            .split_by_table()
+            .iter()
+            .enumerate()
+            .map(|(i, group)| create_change_event(i + 1, group, Some(7)))
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].target(), "public.orders");
+        assert_eq!(events[1].target(), "public.users");
+        for event in &events {
+            assert_eq!(event.cycle, Some(7));
+            assert_eq!(event.row_count, 1);
+            assert_eq!(event.transaction, Some(10));
+        }
+    }
+
+    #[test]
+    fn test_record_content_hash() {
+        let mut meta = TableMeta::new(
+            crate::types::RowIdentity::none(),
+            Vec::new(),
+            FetchStrategy::Full,
+        );
+        meta.content_hash = Some("a".to_string());
+
+        // Unchanged content is not re-queried
+        assert!(!record_content_hash(&mut meta, "a".to_string()));
+        assert_eq!(meta.content_hash.as_deref(), Some("a"));
+
+        assert!(record_content_hash(&mut meta, "b".to_string()));
+        assert_eq!(meta.content_hash.as_deref(), Some("b"));
+        assert!(!record_content_hash(&mut meta, "b".to_string()));
+
+        // Without a baseline hash the relation is fetched
+        meta.content_hash = None;
+        assert!(record_content_hash(&mut meta, "b".to_string()));
+    }
+
+    #[test]
+    fn test_reset_tracking() {
+        let mut meta = TableMeta::new(
+            crate::types::RowIdentity::none(),
+            Vec::new(),
+            FetchStrategy::Full,
+        );
+        meta.boundary = Some(vec!["100".to_string()]);
+        meta.last_key = Some(vec!["100".to_string()]);
+        meta.watermark = Some("700".to_string());
+        for i in 0..5 {
+            meta.fingerprints
+                .insert(vec![i.to_string()], format!("hash{}", i));
+        }
+
+        // Row hashes tracked more rows than counted
+        assert_eq!(reset_tracking(&mut meta, 0, 2), 5);
+        assert_eq!(meta.boundary, None);
+        assert_eq!(meta.last_key, None);
+        assert_eq!(meta.watermark, None);
+        assert!(meta.fingerprints.is_empty());
+
+        // Partially tracked table: the count covers the untracked rows
+        assert_eq!(reset_tracking(&mut meta, 3, 1000), 1000);
+        // Counters not yet updated
+        assert_eq!(reset_tracking(&mut meta, 3, 0), 3);
+    }
+}
@@ -0,0 +1,99 @@
+//! Watch loop state
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::db::get_table_stats;
+use crate::error::TableTraceError;
+use crate::types::{
+    ChangeHistory, ChangeSource, FetchOptions, QuerySpec, TableMetadata, TableSnapshots,
+    TableStats, WatchConfig,
+};
+
+use super::query::take_query_snapshot;
+use super::schema::take_column_fingerprints;
+use super::snapshot::{load_metadata, take_snapshots};
+
+/// State shared between the watch loop and input handlers
+pub struct WatchContext {
+    pub client: Arc<tokio_postgres::Client>,
+    pub source: ChangeSource,
+    pub max_rows: usize,
+    pub fetch: FetchOptions,
+    pub watch_tables: Vec<(String, String)>,
+    pub queries: Vec<QuerySpec>,
+    pub metadata: TableMetadata,
+    pub snapshots: TableSnapshots,
+    pub history: ChangeHistory,
+    pub prev_stats: HashMap<(String, String), TableStats>,
+    /// Watched queries whose last run failed (warned once)
+    pub failing_queries: HashSet<String>,
+}
+
+impl WatchContext {
+    /// Create a new watch context
+    pub fn new(
+        client: tokio_postgres::Client,
+        config: &WatchConfig,
+        watch_tables: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            client: Arc::new(client),
+            source: config.source,
+            max_rows: config.max_rows,
+            fetch: config.fetch.clone(),
+            watch_tables,
+            queries: config.queries.clone(),
+            metadata: TableMetadata::new(),
+            snapshots: Arc::new(Mutex::new(HashMap::new())),
+            history: Arc::new(Mutex::new(Vec::new())),
+            prev_stats: HashMap::new(),
+            failing_queries: HashSet::new(),
+        }
+    }
+
+    /// Load metadata, snapshots and baseline statistics for the watched tables and queries
+    pub async fn take_baseline(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.metadata = load_metadata(&self.client, &self.watch_tables, &self.fetch).await;
+        // Relations without tuple counters are re-queried whatever the source
+        let snapshot_tables: Vec<(String, String)> = self
+            .watch_tables
+            .iter()
+            .filter(|(schema, table)| {
+                self.source.needs_snapshots()
+                    || self
+                        .metadata
+                        .get(&format!("{}.{}", schema, table))
+                        .is_some_and(|meta| !meta.kind.has_counters())
+            })
+            .cloned()
+            .collect();
+        take_snapshots(
+            &self.client,
+            &snapshot_tables,
+            &self.snapshots,
+            &mut self.metadata,
+            self.max_rows,
+        )
+        .await;
+        take_column_fingerprints(&self.client, &self.watch_tables, &mut self.metadata).await;
+        for spec in &self.queries {
+            take_query_snapshot(&self.client, spec, &self.snapshots, &mut self.metadata).await?;
+        }
+        self.prev_stats = get_table_stats(&self.client, &self.watch_tables).await?;
+        Ok(())
+    }
+
+    /// Start watching a query, taking its current result as the baseline
+    pub async fn add_query(&mut self, spec: QuerySpec) -> crate::error::Result<()> {
+        if self.queries.iter().any(|q| q.name == spec.name) {
+            return Err(TableTraceError::config(format!(
+                "Query '{}' is already watched",
+                spec.name
+            )));
+        }
+        take_query_snapshot(&self.client, &spec, &self.snapshots, &mut self.metadata).await?;
+        self.queries.push(spec);
+        Ok(())
+    }
+}
@@ -1,38 +1,33 @@
 //! User input handlers
 
-use std::collections::HashMap;
 use std::sync::atomic::Ordering;
 
-use crate::db::get_table_stats;
 use crate::display::{
     print_prompt, print_table_selection_prompt, print_warning, print_watching_tables,
 };
 use crate::input::{handle_input, parse_selection_input};
 use crate::state::{CHANGE_COUNT, SELECTING_TABLES};
-use crate::types::{ChangeHistory, TableSnapshots, TableStats};
+use crate::types::QuerySpec;
 
-use super::snapshot::take_snapshots;
+use super::context::WatchContext;
 
 /// Process user input
 pub async fn process_user_input(
     rx: &mut tokio::sync::mpsc::Receiver<String>,
     all_tables: &[(String, String)],
-    watch_tables: &mut Vec<(String, String)>,
-    snapshots: &TableSnapshots,
-    history: &ChangeHistory,
-    client: &tokio_postgres::Client,
-    prev_stats: &mut HashMap<(String, String), TableStats>,
+    ctx: &mut WatchContext,
 ) {
     while let Ok(input) = rx.try_recv() {
         eprintln!();
 
         if SELECTING_TABLES.load(Ordering::SeqCst) {
-            if let Some(tables) =
-                handle_table_selection(&input, all_tables, snapshots, history, client, prev_stats)
-                    .await
-            {
-                *watch_tables = tables;
-                print_watching_tables(watch_tables, "✓ Now watching");
+            if handle_table_selection(&input, all_tables, ctx).await {
+                print_watching_tables(
+                    &ctx.watch_tables,
+                    &ctx.queries,
+                    &ctx.metadata,
+                    "✓ Now watching",
+                );
             }
             print_prompt();
             continue;
@@ -40,12 +35,21 @@ pub async fn process_user_input(
 
         let trimmed = input.trim();
         if trimmed == "w" || trimmed == "watching" {
-            print_watching_tables(watch_tables, "👁 Watching");
+            print_watching_tables(&ctx.watch_tables, &ctx.queries, &ctx.metadata, "👁 Watching");
             print_prompt();
             continue;
         }
 
-        handle_input(&input, history);
+        let (command, args) = trimmed
+            .split_once(char::is_whitespace)
+            .unwrap_or((trimmed, ""));
+        if command == "watch-query" {
+            handle_watch_query(args, ctx).await;
+            print_prompt();
+            continue;
+        }
+
+        handle_input(&input, &ctx.history, &ctx.metadata);
 
         if SELECTING_TABLES.load(Ordering::SeqCst) {
             print_table_selection_prompt(all_tables);
@@ -55,24 +59,42 @@ pub async fn process_user_input(
     }
 }
 
-/// Handle table selection
+/// Start watching a query given as `NAME[:KEY]=SQL`
+async fn handle_watch_query(spec: &str, ctx: &mut WatchContext) {
+    let spec = match QuerySpec::parse(spec) {
+        Ok(spec) => spec,
+        Err(e) => {
+            print_warning(&e.to_string());
+            return;
+        }
+    };
+
+    match ctx.add_query(spec).await {
+        Ok(()) => print_watching_tables(
+            &ctx.watch_tables,
+            &ctx.queries,
+            &ctx.metadata,
+            "✓ Now watching",
+        ),
+        Err(e) => print_warning(&e.to_string()),
+    }
+}
+
+/// Handle table selection (returns whether the watched tables changed)
 async fn handle_table_selection(
     input: &str,
     all_tables: &[(String, String)],
-    snapshots: &TableSnapshots,
-    history: &ChangeHistory,
-    client: &tokio_postgres::Client,
-    prev_stats: &mut HashMap<(String, String), TableStats>,
-) -> Option<Vec<(String, String)>> {
+    ctx: &mut WatchContext,
+) -> bool {
     SELECTING_TABLES.store(false, Ordering::SeqCst);
 
     let input = input.trim();
     if input.is_empty() {
         print_warning("Selection cancelled. Continuing with current tables.");
-        return None;
+        return false;
     }
 
-    let new_watch_tables = if input.to_lowercase() == "all" {
+    let new_watch_tables: Vec<(String, String)> = if input.to_lowercase() == "all" {
         all_tables.to_vec()
     } else {
         let indices = parse_selection_input(input, all_tables.len());
@@ -81,21 +103,19 @@ async fn handle_table_selection(
 
     if new_watch_tables.is_empty() {
         print_warning("No valid tables selected. Continuing with current tables.");
-        return None;
+        return false;
     }
 
     // Clear state
-    snapshots.lock().unwrap().clear();
-    history.lock().unwrap().clear();
+    ctx.snapshots.lock().unwrap().clear();
+    ctx.history.lock().unwrap().clear();
     CHANGE_COUNT.store(0, Ordering::Relaxed);
 
-    // Take new snapshots
-    take_snapshots(client, &new_watch_tables, snapshots).await;
-
-    // Update statistics
-    if let Ok(new_stats) = get_table_stats(client, &new_watch_tables).await {
-        *prev_stats = new_stats;
+    // Take new snapshots and update statistics
+    ctx.watch_tables = new_watch_tables;
+    if let Err(e) = ctx.take_baseline().await {
+        print_warning(&format!("Could not take the new baseline: {}", e));
     }
 
-    Some(new_watch_tables)
+    true
 }
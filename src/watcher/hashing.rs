@@ -0,0 +1,136 @@
+//! Row hash fetching
+//!
+//! Keeps only `(identity, md5(row))` fingerprints per table and fetches full
+//! rows only for identities whose hash changed, so wide tables never have to
+//! be held in memory or transferred in full.
+
+use std::collections::HashSet;
+
+use crate::db::{fetch_row_keys, fetch_rows, RowFilter, RowKey};
+use crate::diff::calculate_hash_diffs;
+use crate::types::{RowDiff, TableMeta};
+
+use super::snapshot::{cap_window, window_limit};
+
+/// Take the baseline row hashes of a table
+pub async fn take_fingerprints(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    meta: &mut TableMeta,
+    max_rows: usize,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let keys = fetch_current_hashes(client, schema, table, meta, max_rows).await?;
+    meta.fingerprints = keys
+        .into_iter()
+        .filter_map(|key| Some((key.values, key.hash?)))
+        .collect();
+    Ok(())
+}
+
+/// Compare current row hashes with the stored ones and fetch changed rows
+pub async fn fetch_hash_diffs(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    meta: &mut TableMeta,
+    max_rows: usize,
+) -> Result<Vec<RowDiff>, Box<dyn std::error::Error + Send + Sync>> {
+    let current: Vec<(Vec<String>, String)> =
+        fetch_current_hashes(client, schema, table, meta, max_rows)
+            .await?
+            .into_iter()
+            .filter_map(|key| Some((key.values, key.hash?)))
+            .collect();
+
+    let changed: Vec<String> = current
+        .iter()
+        .filter(|(pk, hash)| meta.fingerprints.get(pk) != Some(hash))
+        .map(|(_, hash)| hash.clone())
+        .collect();
+
+    let changed_rows = if changed.is_empty() {
+        Vec::new()
+    } else {
+        fetch_rows(
+            client,
+            schema,
+            table,
+            meta,
+            meta.boundary.as_deref(),
+            Some(&RowFilter::HashIn(changed)),
+            0,
+        )
+        .await?
+        .into_iter()
+        .map(|(_, row)| row)
+        .collect()
+    };
+
+    let diffs = calculate_hash_diffs(
+        &meta.fingerprints,
+        &current,
+        &changed_rows,
+        &meta.identity.columns,
+        &meta.identity.types,
+    );
+
+    // Rows that changed again before being fetched keep their old hash
+    // (or stay unknown) so they are picked up next cycle
+    let fetched: HashSet<&Vec<String>> = diffs.iter().map(|d| &d.pk_values).collect();
+    // Rows past a lowered boundary leave the tracked window
+    let tracked = if meta.boundary.is_some() {
+        max_rows
+    } else {
+        current.len()
+    };
+    let fingerprints = current
+        .into_iter()
+        .take(tracked)
+        .filter_map(|(pk, hash)| match meta.fingerprints.get(&pk) {
+            Some(old) if old == &hash || fetched.contains(&pk) => Some((pk, hash)),
+            Some(old) => Some((pk, old.clone())),
+            None if fetched.contains(&pk) => Some((pk, hash)),
+            None => None,
+        })
+        .collect();
+    meta.fingerprints = fingerprints;
+
+    Ok(diffs)
+}
+
+/// Fetch identity values and row hashes in the tracked window
+///
+/// Like `fetch_window`, this includes rows of the previous snapshot past a
+/// boundary lowered this cycle.
+async fn fetch_current_hashes(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    meta: &mut TableMeta,
+    max_rows: usize,
+) -> Result<Vec<RowKey>, Box<dyn std::error::Error + Send + Sync>> {
+    let boundary = meta.boundary.clone();
+    let mut keys = fetch_row_keys(
+        client,
+        schema,
+        table,
+        meta,
+        boundary.as_deref(),
+        window_limit(max_rows),
+        true,
+    )
+    .await?;
+
+    let order_keys: Vec<&Vec<String>> = keys.iter().map(|key| &key.order_key).collect();
+    match cap_window(meta, &order_keys, max_rows) {
+        Some(previous_end) => {
+            keys =
+                fetch_row_keys(client, schema, table, meta, Some(&previous_end), 0, true).await?;
+        }
+        None if max_rows > 0 => keys.truncate(max_rows),
+        None => {}
+    }
+
+    Ok(keys)
+}
@@ -0,0 +1,321 @@
+//! Incremental row fetching
+//!
+//! The xmin strategy reads only rows whose `xmin` is at or after the
+//! watermark of the previous fetch and merges them into the previous
+//! snapshot. Deleted rows are found with an identity-only scan.
+//!
+//! The since-column and append-only strategies read rows whose strategy
+//! column lies between the previous and current maximum value, plus rows
+//! written by transactions still running at the previous fetch: those may
+//! commit after rows with a later column value (`updated_at = now()` is the
+//! transaction start time, and sequence values are taken before commit).
+
+use std::collections::HashMap;
+
+use crate::db::{fetch_row_keys, fetch_rows, get_column_max, get_xmin_watermark, RowFilter};
+use crate::diff::get_pk_value;
+use crate::types::{FetchStrategy, RowData, TableMeta};
+
+use super::snapshot::{fetch_window, window_limit};
+
+/// Fetch rows changed since the `since` xid and merge them into `previous`
+///
+/// Returns `None` when the table outgrew the row cap and needs a full fetch
+/// to fix its tracked window.
+pub async fn fetch_changed_rows(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    meta: &mut TableMeta,
+    max_rows: usize,
+    previous: &[RowData],
+    since: &str,
+) -> Result<Option<Vec<RowData>>, Box<dyn std::error::Error + Send + Sync>> {
+    let boundary = meta.boundary.clone();
+    let limit = window_limit(max_rows);
+
+    let keys = fetch_row_keys(
+        client,
+        schema,
+        table,
+        meta,
+        boundary.as_deref(),
+        limit,
+        false,
+    )
+    .await?;
+    if limit > 0 && keys.len() > max_rows {
+        return Ok(None);
+    }
+
+    let filter = RowFilter::ChangedSince(since.to_string());
+    let changed = fetch_rows(
+        client,
+        schema,
+        table,
+        meta,
+        boundary.as_deref(),
+        Some(&filter),
+        0,
+    )
+    .await?;
+
+    meta.last_key = keys.last().map(|key| key.order_key.clone());
+
+    let current_keys: Vec<Vec<String>> = keys.into_iter().map(|key| key.values).collect();
+    let changed_rows = changed.into_iter().map(|(_, row)| row).collect();
+    Ok(Some(merge_rows(
+        previous,
+        &current_keys,
+        changed_rows,
+        &meta.identity.columns,
+    )))
+}
+
+/// Fetch rows for the since-column and append-only strategies
+///
+/// Without a `previous` snapshot this takes the baseline: the tracked window
+/// for since-column tables and no rows for append-only tables.
+pub async fn fetch_column_slice(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    meta: &mut TableMeta,
+    max_rows: usize,
+    previous: Option<&[RowData]>,
+) -> Result<Vec<RowData>, Box<dyn std::error::Error + Send + Sync>> {
+    let (column, column_type, inclusive) = match &meta.strategy {
+        FetchStrategy::SinceColumn {
+            column,
+            column_type,
+        } => (column.clone(), column_type.clone(), true),
+        FetchStrategy::Keyset {
+            column,
+            column_type,
+        } => (column.clone(), column_type.clone(), false),
+        FetchStrategy::Full | FetchStrategy::Xmin | FetchStrategy::RowHash => return Ok(Vec::new()),
+    };
+    let append_only = !inclusive;
+
+    // Taken first so that rows committed meanwhile are fetched next cycle
+    let horizon = get_xmin_watermark(client).await?;
+    let to = get_column_max(client, schema, table, &column).await?;
+
+    if previous.is_none() {
+        meta.watermark = to;
+        meta.xmin_horizon = Some(horizon);
+        return if append_only {
+            Ok(Vec::new())
+        } else {
+            fetch_window(client, schema, table, meta, max_rows).await
+        };
+    }
+
+    let Some(to) = to else {
+        return Ok(Vec::new());
+    };
+
+    let filter = RowFilter::ColumnRange {
+        column,
+        column_type,
+        from: meta.watermark.clone(),
+        inclusive,
+        to: to.clone(),
+        written_since: meta.xmin_horizon.clone(),
+    };
+    // Since-column tables stay within the tracked window
+    let boundary = if append_only {
+        None
+    } else {
+        meta.boundary.clone()
+    };
+    let rows = fetch_rows(
+        client,
+        schema,
+        table,
+        meta,
+        boundary.as_deref(),
+        Some(&filter),
+        0,
+    )
+    .await?;
+
+    meta.watermark = Some(to);
+    meta.xmin_horizon = Some(horizon);
+    Ok(rows.into_iter().map(|(_, row)| row).collect())
+}
+
+/// Snapshot kept after a fetch with the given strategy
+///
+/// Since-column fetches are upserted, and append-only tables keep only the
+/// last fetched rows so that rows read again are not reported twice.
+pub fn update_snapshot(
+    strategy: &FetchStrategy,
+    previous: Vec<RowData>,
+    fetched: Vec<RowData>,
+    pk_cols: &[String],
+) -> Vec<RowData> {
+    match strategy {
+        FetchStrategy::Full | FetchStrategy::Xmin | FetchStrategy::Keyset { .. } => fetched,
+        FetchStrategy::SinceColumn { .. } => upsert_rows(previous, &fetched, pk_cols),
+        FetchStrategy::RowHash => Vec::new(),
+    }
+}
+
+/// Insert or replace rows of a snapshot by key
+fn upsert_rows(previous: Vec<RowData>, changed: &[RowData], pk_cols: &[String]) -> Vec<RowData> {
+    let mut index: HashMap<Vec<String>, usize> = previous
+        .iter()
+        .enumerate()
+        .map(|(i, row)| (get_pk_value(row, pk_cols), i))
+        .collect();
+    let mut rows = previous;
+
+    for row in changed {
+        let key = get_pk_value(row, pk_cols);
+        match index.get(&key) {
+            Some(&i) => rows[i] = row.clone(),
+            None => {
+                index.insert(key, rows.len());
+                rows.push(row.clone());
+            }
+        }
+    }
+
+    rows
+}
+
+/// Merge changed rows into a snapshot
+///
+/// The result holds one row per key in `current_keys` (in that order), taken
+/// from `changed` if present and otherwise from `previous`.
+pub fn merge_rows(
+    previous: &[RowData],
+    current_keys: &[Vec<String>],
+    changed: Vec<RowData>,
+    pk_cols: &[String],
+) -> Vec<RowData> {
+    let mut by_key: HashMap<Vec<String>, RowData> = previous
+        .iter()
+        .map(|row| (get_pk_value(row, pk_cols), row.clone()))
+        .collect();
+    for row in changed {
+        by_key.insert(get_pk_value(&row, pk_cols), row);
+    }
+
+    current_keys
+        .iter()
+        .filter_map(|key| by_key.remove(key))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::calculate_all_diffs;
+    use crate::value::Value;
+
+    fn create_row(pairs: &[(&str, &str)]) -> RowData {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), Value::Text(v.to_string())))
+            .collect()
+    }
+
+    fn keys(ids: &[&str]) -> Vec<Vec<String>> {
+        ids.iter().map(|id| vec![id.to_string()]).collect()
+    }
+
+    #[test]
+    fn test_merge_rows() {
+        let pk_cols = vec!["id".to_string()];
+        let previous = vec![
+            create_row(&[("id", "1"), ("name", "Alice")]),
+            create_row(&[("id", "2"), ("name", "Bob")]),
+            create_row(&[("id", "3"), ("name", "Carol")]),
+        ];
+        let changed = vec![
+            create_row(&[("id", "2"), ("name", "Robert")]),
+            create_row(&[("id", "4"), ("name", "Dave")]),
+        ];
+
+        // Row 3 was deleted, row 2 updated and row 4 inserted
+        let merged = merge_rows(&previous, &keys(&["1", "2", "4"]), changed, &pk_cols);
+
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0]["name"], Value::Text("Alice".to_string()));
+        assert_eq!(merged[1]["name"], Value::Text("Robert".to_string()));
+        assert_eq!(merged[2]["name"], Value::Text("Dave".to_string()));
+    }
+
+    #[test]
+    fn test_merge_skips_rows_missing_from_key_scan() {
+        let pk_cols = vec!["id".to_string()];
+        let previous = vec![create_row(&[("id", "1"), ("name", "Alice")])];
+        let changed = vec![create_row(&[("id", "5"), ("name", "Eve")])];
+
+        // Row 5 was inserted after the key scan; it is picked up next cycle
+        let merged = merge_rows(&previous, &keys(&["1"]), changed, &pk_cols);
+
+        assert_eq!(merged, previous);
+    }
+
+    #[test]
+    fn test_update_snapshot() {
+        let pk_cols = vec!["id".to_string()];
+        let previous = vec![
+            create_row(&[("id", "1"), ("name", "Alice")]),
+            create_row(&[("id", "2"), ("name", "Bob")]),
+        ];
+        let fetched = vec![
+            create_row(&[("id", "2"), ("name", "Robert")]),
+            create_row(&[("id", "3"), ("name", "Carol")]),
+        ];
+
+        // A since-column slice is upserted: rows outside it are kept
+        let since = FetchStrategy::SinceColumn {
+            column: "updated_at".to_string(),
+            column_type: "timestamptz".to_string(),
+        };
+        let upserted = update_snapshot(&since, previous.clone(), fetched.clone(), &pk_cols);
+        assert_eq!(upserted.len(), 3);
+        assert_eq!(upserted[0]["name"], Value::Text("Alice".to_string()));
+        assert_eq!(upserted[1]["name"], Value::Text("Robert".to_string()));
+
+        // A full fetch is authoritative: rows missing from it were deleted
+        let replaced = update_snapshot(&FetchStrategy::Xmin, previous, fetched.clone(), &pk_cols);
+        assert_eq!(replaced, fetched);
+    }
+
+    #[test]
+    fn test_append_only_rows_read_again() {
+        let pk_cols = vec!["id".to_string()];
+        let strategy = FetchStrategy::Keyset {
+            column: "id".to_string(),
+            column_type: "bigint".to_string(),
+        };
+        let first = vec![create_row(&[("id", "1")]), create_row(&[("id", "3")])];
+        let snapshot = update_snapshot(&strategy, Vec::new(), first.clone(), &pk_cols);
+
+        // Row 2 committed after row 3; the overlap reads rows 1 and 3 again
+        let second = vec![
+            create_row(&[("id", "1")]),
+            create_row(&[("id", "2")]),
+            create_row(&[("id", "3")]),
+            create_row(&[("id", "4")]),
+        ];
+        let diffs = calculate_all_diffs(&snapshot, &second, &pk_cols, strategy.diff_scope());
+        let added: Vec<&str> = diffs
+            .iter()
+            .map(|d| {
+                assert_eq!(d.change_type, "added");
+                d.pk_values[0].as_str()
+            })
+            .collect();
+        assert_eq!(added, vec!["2", "4"]);
+        assert_eq!(
+            update_snapshot(&strategy, first, second.clone(), &pk_cols),
+            second
+        );
+    }
+}
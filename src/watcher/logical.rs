@@ -0,0 +1,63 @@
+//! Logical decoding change source
+
+use crate::constants::db::LOGICAL_SLOT_PREFIX;
+use crate::db::{create_logical_slot, get_slot_changes, get_wal_level};
+use crate::decoding::{parse_begin, parse_change, parse_commit, parse_truncate, DecodedChange};
+use crate::error::{Result, TableTraceError};
+
+use super::changes::CycleResult;
+use super::context::WatchContext;
+use super::stream::collect_stream_changes;
+
+/// Change source backed by a temporary logical replication slot
+pub struct LogicalSource {
+    slot_name: String,
+}
+
+impl LogicalSource {
+    /// Check server support and create the replication slot
+    pub async fn start(client: &tokio_postgres::Client) -> Result<Self> {
+        let wal_level = get_wal_level(client).await?;
+        if wal_level != "logical" {
+            return Err(TableTraceError::LogicalDecodingUnavailable(wal_level));
+        }
+
+        let slot_name = format!("{}{}", LOGICAL_SLOT_PREFIX, std::process::id());
+        create_logical_slot(client, &slot_name).await?;
+
+        Ok(Self { slot_name })
+    }
+
+    /// Collect changes committed since the previous call
+    pub async fn collect_changes(&mut self, ctx: &WatchContext) -> Result<CycleResult> {
+        let lines = get_slot_changes(&ctx.client, &self.slot_name).await?;
+        let mut changes = Vec::new();
+        let mut commits = Vec::new();
+        let mut xid = None;
+        for line in &lines {
+            if let Some(begin) = parse_begin(line) {
+                xid = Some(begin);
+            } else if let Some(commit) = parse_commit(line) {
+                commits.push(commit);
+            } else {
+                let decoded = match parse_change(line) {
+                    Some(change) => vec![change],
+                    None => parse_truncate(line).unwrap_or_default(),
+                };
+                changes.extend(
+                    decoded
+                        .into_iter()
+                        .map(|change| DecodedChange { xid, ..change }),
+                );
+            }
+        }
+
+        let mut result = collect_stream_changes(ctx, changes);
+        for (xid, committed_at) in commits {
+            if result.transactions.iter().any(|(id, _)| *id == xid) {
+                result.add_transaction(xid, committed_at);
+            }
+        }
+        Ok(result)
+    }
+}
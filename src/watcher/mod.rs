@@ -2,35 +2,54 @@
 //!
 //! Provides functionality for monitoring table changes.
 
+mod catalog;
 mod changes;
+mod context;
 mod handlers;
+mod hashing;
+mod incremental;
+mod logical;
+mod query;
+mod schema;
 mod snapshot;
 mod stats;
+mod stream;
+mod transactions;
+mod triggers;
 
-use std::collections::HashMap;
 use std::io::{self, Write};
 use std::sync::atomic::Ordering;
-use std::sync::{Arc, Mutex};
 
-use tokio_postgres::NoTls;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio_postgres::{AsyncMessage, NoTls, Socket};
 use tracing::error;
 
 use crate::constants::display::PROMPT_CLEAR_WIDTH;
-use crate::db::{get_all_tables, get_table_stats, has_stats_changes};
+use crate::db::{get_all_tables, get_table_stats};
 use crate::display::{
     print_banner, print_change_line, print_connected, print_connecting, print_connection_error,
-    print_history, print_interactive_hint, print_prompt, print_warning, print_watching_tables,
+    print_history, print_interactive_hint, print_prompt, print_success, print_warning,
+    print_watching_tables,
 };
-use crate::state::{CHANGE_COUNT, CONNECTION_LOST};
-use crate::types::{ChangeHistory, TableSnapshots, WatchConfig};
+use crate::state::{CHANGE_COUNT, CONNECTION_LOST, TIME_DISPLAY};
+use crate::types::{ChangeSource, EventGrouping, WatchConfig};
 
-use changes::{collect_cycle_changes, create_change_event};
+use catalog::CatalogWatcher;
+use changes::{collect_cycle_changes, collect_requery_changes, create_change_event, CycleResult};
+use context::WatchContext;
 use handlers::process_user_input;
-use snapshot::{select_initial_tables, setup_input_channel, take_snapshots};
-use stats::debounce_stats;
+use logical::LogicalSource;
+use query::collect_query_changes;
+use schema::collect_schema_changes;
+use snapshot::{select_initial_tables, setup_input_channel};
+use stats::{debounce_stats, has_stats_changes};
+use stream::StreamSource;
+use transactions::assign_transactions;
+use triggers::TriggerSource;
 
 /// Main watch loop
 pub async fn watch_tables(config: WatchConfig) -> Result<(), Box<dyn std::error::Error>> {
+    TIME_DISPLAY.set(config.time.clone()).ok();
     print_banner();
     print_connecting();
 
@@ -39,45 +58,66 @@ pub async fn watch_tables(config: WatchConfig) -> Result<(), Box<dyn std::error:
     let (client, connection) = tokio_postgres::connect(&conn_str, NoTls).await?;
 
     // Maintain connection in separate task
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            error!("Connection error: {}", e);
-            CONNECTION_LOST.store(true, Ordering::SeqCst);
-        }
-    });
+    let notifications = spawn_connection(connection);
 
     print_connected();
 
     // Get available tables
     let all_tables = get_all_tables(&client, &config.schema).await?;
-    if all_tables.is_empty() {
+    if all_tables.is_empty() && config.queries.is_empty() {
         print_warning("No tables found in database.");
         return Ok(());
     }
 
-    // Select tables to watch
-    let mut watch_tables = select_initial_tables(&all_tables, config.interactive).await?;
-    if watch_tables.is_empty() {
+    // Select tables to watch (optional when queries are watched)
+    let watch_tables = if all_tables.is_empty() {
+        Vec::new()
+    } else {
+        select_initial_tables(&all_tables, config.interactive).await?
+    };
+    if watch_tables.is_empty() && config.queries.is_empty() {
         print_warning("No tables selected to watch.");
         return Ok(());
     }
 
-    print_watching_tables(&watch_tables, "👁 Watching");
-
-    if config.interactive {
-        print_interactive_hint();
-    }
-
     // Initialize shared state
-    let history: ChangeHistory = Arc::new(Mutex::new(Vec::new()));
-    let snapshots: TableSnapshots = Arc::new(Mutex::new(HashMap::new()));
-    let client_arc = Arc::new(client);
+    let mut ctx = WatchContext::new(client, &config, watch_tables);
 
-    // Take initial snapshots
-    take_snapshots(&client_arc, &watch_tables, &snapshots).await;
+    // Start row change sources before the first poll so no change is missed
+    let mut stream_source = match config.source {
+        ChangeSource::Logical => Some(StreamSource::Logical(
+            LogicalSource::start(&ctx.client).await?,
+        )),
+        ChangeSource::Triggers => Some(StreamSource::Triggers(
+            TriggerSource::start(&ctx.client, notifications).await?,
+        )),
+        ChangeSource::Polling => None,
+    };
 
-    let mut prev_stats = get_table_stats(&client_arc, &watch_tables).await?;
+    // Take initial snapshots
+    if let Err(e) = ctx.take_baseline().await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    let mut catalog = if config.catalog {
+        Some(CatalogWatcher::start(&ctx.client, &config.schema).await?)
+    } else {
+        None
+    };
     let mut change_counter: usize = 0;
+    let mut cycle_counter: usize = 0;
+
+    print_watching_tables(&ctx.watch_tables, &ctx.queries, &ctx.metadata, "👁 Watching");
+    if let Some(catalog) = &catalog {
+        print_success(&format!(
+            "Watching {} catalog objects (functions, triggers, policies, indexes, constraints, grants)\n",
+            catalog.len()
+        ));
+    }
+
+    if config.interactive {
+        print_interactive_hint();
+    }
 
     // Setup input channel
     let mut rx = setup_input_channel(config.interactive);
@@ -88,81 +128,191 @@ pub async fn watch_tables(config: WatchConfig) -> Result<(), Box<dyn std::error:
     // Main loop
     loop {
         if config.interactive {
-            process_user_input(
-                &mut rx,
-                &all_tables,
-                &mut watch_tables,
-                &snapshots,
-                &history,
-                &client_arc,
-                &mut prev_stats,
-            )
-            .await;
+            process_user_input(&mut rx, &all_tables, &mut ctx).await;
         }
 
         tokio::time::sleep(tokio::time::Duration::from_millis(config.interval)).await;
 
-        // Get statistics
-        let current_stats = match get_table_stats(&client_arc, &watch_tables).await {
-            Ok(stats) => stats,
-            Err(e) => {
-                print_connection_error(e.as_ref());
-                std::process::exit(1);
-            }
+        // Collect and display changes (column changes first, so rows decode with the new ones)
+        let mut cycle_result = collect_schema_changes(
+            &ctx.watch_tables,
+            &ctx.snapshots,
+            &mut ctx.metadata,
+            &ctx.fetch,
+            ctx.max_rows,
+            &ctx.client,
+        )
+        .await;
+        let source_result = match stream_source.as_mut() {
+            Some(source) => match source.collect_changes(&ctx).await {
+                Ok(result) => result,
+                Err(e) => {
+                    print_connection_error(&e);
+                    std::process::exit(1);
+                }
+            },
+            None => poll_stats_changes(&mut ctx, &cycle_result.tables, config.interactive).await,
         };
+        cycle_result.merge(source_result);
+        cycle_result.merge(
+            collect_requery_changes(
+                &ctx.watch_tables,
+                &ctx.snapshots,
+                &mut ctx.metadata,
+                ctx.max_rows,
+                &ctx.client,
+            )
+            .await,
+        );
+        cycle_result.merge(collect_query_changes(&mut ctx, config.interactive).await);
+        if let Some(catalog) = catalog.as_mut() {
+            cycle_result.merge(catalog.collect_changes(&ctx.client).await);
+        }
 
-        // Debounce
-        let final_stats = if has_stats_changes(&current_stats, &prev_stats) {
-            debounce_stats(&client_arc, &watch_tables, &current_stats).await
+        let results = match config.grouping {
+            EventGrouping::Cycle => vec![cycle_result],
+            EventGrouping::Transaction => {
+                if stream_source.is_none() {
+                    assign_transactions(
+                        &mut cycle_result,
+                        &ctx.watch_tables,
+                        &ctx.metadata,
+                        &ctx.client,
+                    )
+                    .await;
+                }
+                cycle_result.split_by_transaction()
+            }
+        };
+        let results: Vec<_> = if config.per_table {
+            results
+                .into_iter()
+                .flat_map(CycleResult::split_by_table)
+                .collect()
         } else {
-            current_stats.clone()
+            results
+        };
+        // Entries split per table share the number of their cycle
+        let cycle = if config.per_table && results.iter().any(|r| r.has_changes()) {
+            cycle_counter += 1;
+            Some(cycle_counter)
+        } else {
+            None
         };
 
-        // Collect and display changes
-        let cycle_result =
-            collect_cycle_changes(&final_stats, &prev_stats, &snapshots, &client_arc).await;
-
-        if !cycle_result.diffs.is_empty() {
+        let mut recorded = 0;
+        for result in results.into_iter().filter(|r| r.has_changes()) {
             change_counter += 1;
+            recorded += 1;
             CHANGE_COUNT.store(change_counter, Ordering::Relaxed);
 
-            let change = create_change_event(
-                change_counter,
-                &cycle_result.tables,
-                &cycle_result.change_types,
-                cycle_result.total_rows,
-            );
+            let change = create_change_event(change_counter, &result, cycle);
 
             // Create record for display and history
             let record = crate::types::ChangeRecord {
                 change,
-                diffs: cycle_result.diffs,
+                notes: result.notes(),
+                diffs: result.diffs,
+                definitions: result.definitions,
             };
 
-            // Add to history first
-            {
-                let mut h = history.lock().unwrap();
-                h.push(record);
-                if h.len() > crate::constants::display::MAX_HISTORY_SIZE {
-                    h.remove(0);
-                }
+            let mut h = ctx.history.lock().unwrap();
+            h.push(record);
+            if h.len() > crate::constants::display::MAX_HISTORY_SIZE {
+                h.remove(0);
             }
+        }
 
+        if recorded > 0 {
             // Display full history
             if config.interactive {
                 eprintln!("\r{}", " ".repeat(PROMPT_CLEAR_WIDTH));
-                print_history(&history);
+                print_history(&ctx.history);
                 print_prompt();
             } else {
-                // Non-interactive: just show the latest change
-                let h = history.lock().unwrap();
-                if let Some(r) = h.last() {
+                // Non-interactive: just show the new changes
+                let h = ctx.history.lock().unwrap();
+                for r in &h[h.len().saturating_sub(recorded)..] {
                     print_change_line(r, "");
                 }
             }
         }
 
-        prev_stats = final_stats;
         io::stdout().flush().ok();
     }
 }
+
+/// Drive the connection in a separate task, forwarding NOTIFY payloads
+fn spawn_connection(
+    mut connection: tokio_postgres::Connection<Socket, tokio_postgres::tls::NoTlsStream>,
+) -> UnboundedReceiver<String> {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        loop {
+            match std::future::poll_fn(|cx| connection.poll_message(cx)).await {
+                Some(Ok(AsyncMessage::Notification(n))) => {
+                    tx.send(n.payload().to_string()).ok();
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    error!("Connection error: {}", e);
+                    CONNECTION_LOST.store(true, Ordering::SeqCst);
+                    break;
+                }
+                None => break,
+            }
+        }
+    });
+
+    rx
+}
+
+/// Detect changes from table statistics and diff the changed tables
+async fn poll_stats_changes(
+    ctx: &mut WatchContext,
+    diffed: &[String],
+    interactive: bool,
+) -> CycleResult {
+    // Get statistics
+    let current_stats = match get_table_stats(&ctx.client, &ctx.watch_tables).await {
+        Ok(stats) => stats,
+        Err(e) => {
+            print_connection_error(e.as_ref());
+            std::process::exit(1);
+        }
+    };
+
+    // Debounce
+    let final_stats = if has_stats_changes(&current_stats, &ctx.prev_stats) {
+        debounce_stats(&ctx.client, &ctx.watch_tables, &current_stats).await
+    } else {
+        current_stats
+    };
+
+    let result = collect_cycle_changes(
+        &final_stats,
+        &ctx.prev_stats,
+        diffed,
+        &ctx.snapshots,
+        &mut ctx.metadata,
+        ctx.max_rows,
+        &ctx.client,
+    )
+    .await;
+    ctx.prev_stats = final_stats;
+
+    if !result.stats_reset.is_empty() {
+        if interactive {
+            eprintln!("\r{}", " ".repeat(PROMPT_CLEAR_WIDTH));
+        }
+        print_warning(&format!(
+            "⚠ Statistics were reset (pg_stat_reset() or server restart) for {}: re-baselined from the current rows",
+            result.stats_reset.join(", ")
+        ));
+        if interactive {
+            print_prompt();
+        }
+    }
+    result
+}
@@ -0,0 +1,274 @@
+//! Watched queries
+//!
+//! A read-only query result is treated as a virtual table: it is
+//! re-evaluated each cycle and diffed against the previous result.
+
+use crate::constants::display::PROMPT_CLEAR_WIDTH;
+use crate::db::{describe_query, fetch_query_rows};
+use crate::diff::{calculate_all_diffs, compare_row_keys, get_pk_value};
+use crate::display::{print_prompt, print_warning};
+use crate::error::{Result, TableTraceError};
+use crate::types::{
+    Column, DiffScope, FetchStrategy, IdentityStrategy, QuerySpec, RelationKind, RowData, RowDiff,
+    RowIdentity, TableMeta, TableMetadata, TableSnapshots,
+};
+
+use super::changes::CycleResult;
+use super::context::WatchContext;
+
+/// Describe a query and take its first result as the baseline
+pub async fn take_query_snapshot(
+    client: &tokio_postgres::Client,
+    spec: &QuerySpec,
+    snapshots: &TableSnapshots,
+    metadata: &mut TableMetadata,
+) -> Result<()> {
+    let invalid = |e: tokio_postgres::Error| {
+        TableTraceError::InvalidQuery(spec.name.clone(), error_reason(&e))
+    };
+
+    let columns = describe_query(client, &spec.sql).await.map_err(invalid)?;
+    let identity = query_identity(spec, &columns)?;
+
+    let rows = fetch_query_rows(client, &spec.sql, &columns)
+        .await
+        .map_err(invalid)?;
+    if let Some(key) = duplicate_key(&rows, &identity.columns) {
+        return Err(TableTraceError::InvalidQuery(
+            spec.name.clone(),
+            format!(
+                "key ({}) is not unique in the result: ({}) appears more than once",
+                identity.columns.join(", "),
+                key.join(", ")
+            ),
+        ));
+    }
+    snapshots.lock().unwrap().insert(spec.name.clone(), rows);
+    metadata.insert(
+        spec.name.clone(),
+        TableMeta {
+            kind: RelationKind::Query,
+            ..TableMeta::new(identity, columns, FetchStrategy::Full)
+        },
+    );
+    Ok(())
+}
+
+/// Re-run watched queries and diff their results against the previous ones
+///
+/// A failing query is skipped with a warning, shown once until it runs
+/// again. When its key stops being unique, its rows are compared as a whole
+/// from then on.
+pub async fn collect_query_changes(ctx: &mut WatchContext, interactive: bool) -> CycleResult {
+    let mut result = CycleResult::default();
+    let mut warnings = Vec::new();
+
+    for spec in &ctx.queries {
+        let Some(meta) = ctx.metadata.get_mut(&spec.name) else {
+            continue;
+        };
+        let new_rows = match fetch_query_rows(&ctx.client, &spec.sql, &meta.columns).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                if ctx.failing_queries.insert(spec.name.clone()) {
+                    warnings.push(format!(
+                        "⚠ Query '{}' failed, skipped until it runs again: {}",
+                        spec.name,
+                        error_reason(&e)
+                    ));
+                }
+                continue;
+            }
+        };
+        ctx.failing_queries.remove(&spec.name);
+
+        if let Some(key) = duplicate_key(&new_rows, &meta.identity.columns) {
+            warnings.push(format!(
+                "⚠ Key ({}) of query '{}' is no longer unique: ({}) appears more than once, rows are compared as a whole",
+                meta.identity.columns.join(", "),
+                spec.name,
+                key.join(", ")
+            ));
+            meta.identity = RowIdentity::none();
+        }
+
+        let old_rows = ctx
+            .snapshots
+            .lock()
+            .unwrap()
+            .insert(spec.name.clone(), new_rows.clone())
+            .unwrap_or_default();
+        let diffs = diff_query_rows(&spec.name, &old_rows, &new_rows, &meta.identity.columns);
+        if !diffs.is_empty() {
+            result.count_diffs(&diffs);
+            result.push_table(spec.name.clone(), diffs);
+        }
+    }
+
+    if !warnings.is_empty() {
+        if interactive {
+            eprintln!("\r{}", " ".repeat(PROMPT_CLEAR_WIDTH));
+        }
+        for warning in &warnings {
+            print_warning(warning);
+        }
+        if interactive {
+            print_prompt();
+        }
+    }
+    result
+}
+
+/// Message of a query error, without the driver's prefix for server errors
+fn error_reason(e: &tokio_postgres::Error) -> String {
+    match e.as_db_error() {
+        Some(db) => db.message().to_string(),
+        None => e.to_string(),
+    }
+}
+
+/// Row identity of a query result from its key columns
+fn query_identity(spec: &QuerySpec, columns: &[Column]) -> Result<RowIdentity> {
+    if spec.key.is_empty() {
+        return Ok(RowIdentity::none());
+    }
+    let mut types = Vec::new();
+    for key in &spec.key {
+        match columns.iter().find(|c| c.name == *key) {
+            Some(column) => types.push(column.type_name.clone()),
+            None => {
+                return Err(TableTraceError::InvalidQuery(
+                    spec.name.clone(),
+                    format!("key column '{}' is not in the result", key),
+                ))
+            }
+        }
+    }
+    Ok(RowIdentity {
+        strategy: IdentityStrategy::QueryKey,
+        columns: spec.key.clone(),
+        types,
+    })
+}
+
+/// First key value shared by several rows, if any
+fn duplicate_key(rows: &[RowData], key: &[String]) -> Option<Vec<String>> {
+    if key.is_empty() {
+        return None;
+    }
+    let mut sorted: Vec<&RowData> = rows.iter().collect();
+    sorted.sort_by(|a, b| compare_row_keys(a, b, key));
+    sorted
+        .windows(2)
+        .find(|w| compare_row_keys(w[0], w[1], key).is_eq())
+        .map(|w| get_pk_value(w[0], key))
+}
+
+/// Diff two results of a query, named after the query
+fn diff_query_rows(
+    name: &str,
+    old_rows: &[RowData],
+    new_rows: &[RowData],
+    key: &[String],
+) -> Vec<RowDiff> {
+    let mut diffs = calculate_all_diffs(old_rows, new_rows, key, DiffScope::Full);
+    for diff in &mut diffs {
+        diff.table = name.to_string();
+    }
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::Value;
+
+    fn column(attnum: i16, name: &str, type_name: &str) -> Column {
+        Column {
+            attnum,
+            name: name.to_string(),
+            type_oid: 0,
+            type_name: type_name.to_string(),
+        }
+    }
+
+    fn row(status: &str, jobs: i64) -> RowData {
+        [
+            ("status".to_string(), Value::Text(status.to_string())),
+            ("jobs".to_string(), Value::Int(jobs)),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn key(cols: &[&str]) -> Vec<String> {
+        cols.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_query_identity() {
+        let columns = vec![column(1, "status", "text"), column(2, "jobs", "bigint")];
+
+        let spec = QuerySpec::parse("jobs:status=SELECT 1").unwrap();
+        let identity = query_identity(&spec, &columns).unwrap();
+        assert_eq!(identity.strategy, IdentityStrategy::QueryKey);
+        assert_eq!(identity.columns, vec!["status"]);
+        assert_eq!(identity.types, vec!["text"]);
+
+        let spec = QuerySpec::parse("jobs=SELECT 1").unwrap();
+        let identity = query_identity(&spec, &columns).unwrap();
+        assert_eq!(identity.strategy, IdentityStrategy::None);
+        assert!(identity.columns.is_empty());
+
+        let spec = QuerySpec::parse("jobs:status,id=SELECT 1").unwrap();
+        match query_identity(&spec, &columns) {
+            Err(TableTraceError::InvalidQuery(name, reason)) => {
+                assert_eq!(name, "jobs");
+                assert_eq!(reason, "key column 'id' is not in the result");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_duplicate_key() {
+        let rows = vec![row("done", 3), row("queued", 1), row("done", 4)];
+        assert_eq!(
+            duplicate_key(&rows, &key(&["status"])),
+            Some(vec!["done".to_string()])
+        );
+        assert_eq!(duplicate_key(&rows, &key(&["status", "jobs"])), None);
+        // Without a key, rows are compared as a whole and may repeat
+        assert_eq!(duplicate_key(&rows, &[]), None);
+        assert_eq!(duplicate_key(&[], &key(&["status"])), None);
+    }
+
+    #[test]
+    fn test_diff_query_rows() {
+        let old_rows = vec![row("done", 3), row("queued", 1)];
+        let new_rows = vec![row("running", 1), row("done", 4)];
+
+        let diffs = diff_query_rows("jobs", &old_rows, &new_rows, &key(&["status"]));
+        let summary: Vec<(&str, &str)> = diffs
+            .iter()
+            .map(|d| (d.pk_values[0].as_str(), d.change_type.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("done", "modified"),
+                ("queued", "removed"),
+                ("running", "added")
+            ]
+        );
+        assert!(diffs.iter().all(|d| d.table == "jobs"));
+        assert_eq!(diffs[0].changed_columns, vec!["jobs"]);
+
+        // Without a key, a changed row is removed and added
+        let diffs = diff_query_rows("jobs", &old_rows[..1], &new_rows[1..], &[]);
+        let mut types: Vec<&str> = diffs.iter().map(|d| d.change_type.as_str()).collect();
+        types.sort();
+        assert_eq!(types, vec!["added", "removed"]);
+        assert!(diff_query_rows("jobs", &old_rows, &old_rows, &[]).is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+//! Schema change detection
+//!
+//! The column list of each watched table is fingerprinted every cycle.
+//! When it changes, the column-level delta is reported and the table's
+//! snapshot is brought up to date, diffing only the columns that exist
+//! with the same type before and after the change.
+
+use std::fmt;
+
+use crate::db::{get_column_fingerprints, get_columns, get_foreign_keys, get_row_identity};
+use crate::types::{
+    Column, FetchOptions, FetchStrategy, RowData, RowDiff, RowIdentity, TableMeta, TableMetadata,
+    TableSnapshots,
+};
+use crate::value::Value;
+
+use super::changes::{calculate_table_diffs, CycleResult};
+use super::hashing::take_fingerprints;
+use super::snapshot::{resolve_strategy, take_table_snapshot};
+
+/// Column-level change between two versions of a table
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnChange {
+    Added {
+        name: String,
+        type_name: String,
+    },
+    Dropped {
+        name: String,
+    },
+    Renamed {
+        from: String,
+        to: String,
+    },
+    TypeChanged {
+        name: String,
+        from: String,
+        to: String,
+    },
+}
+
+impl fmt::Display for ColumnChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added { name, type_name } => write!(f, "column {} added ({})", name, type_name),
+            Self::Dropped { name } => write!(f, "column {} dropped", name),
+            Self::Renamed { from, to } => write!(f, "column {} renamed to {}", from, to),
+            Self::TypeChanged { name, from, to } => {
+                write!(f, "column {} type changed: {} → {}", name, from, to)
+            }
+        }
+    }
+}
+
+/// Compare two column lists by column number
+pub fn column_changes(old: &[Column], new: &[Column]) -> Vec<ColumnChange> {
+    let mut changes = Vec::new();
+
+    for before in old {
+        match new.iter().find(|c| c.attnum == before.attnum) {
+            None => changes.push(ColumnChange::Dropped {
+                name: before.name.clone(),
+            }),
+            Some(after) => {
+                if after.name != before.name {
+                    changes.push(ColumnChange::Renamed {
+                        from: before.name.clone(),
+                        to: after.name.clone(),
+                    });
+                }
+                if after.type_name != before.type_name {
+                    changes.push(ColumnChange::TypeChanged {
+                        name: after.name.clone(),
+                        from: before.type_name.clone(),
+                        to: after.type_name.clone(),
+                    });
+                }
+            }
+        }
+    }
+    for after in new {
+        if !old.iter().any(|c| c.attnum == after.attnum) {
+            changes.push(ColumnChange::Added {
+                name: after.name.clone(),
+                type_name: after.type_name.clone(),
+            });
+        }
+    }
+
+    changes
+}
+
+/// Names (after the change) of the columns kept with the same type
+fn common_columns(old: &[Column], new: &[Column]) -> Vec<String> {
+    new.iter()
+        .filter(|after| {
+            old.iter()
+                .any(|before| before.attnum == after.attnum && before.type_name == after.type_name)
+        })
+        .map(|c| c.name.clone())
+        .collect()
+}
+
+/// Rename columns in snapshot rows, keeping their position
+fn rename_columns(rows: &mut [RowData], changes: &[ColumnChange]) {
+    for change in changes {
+        let ColumnChange::Renamed { from, to } = change else {
+            continue;
+        };
+        for row in rows.iter_mut() {
+            if let Some(index) = row.get_index_of(from) {
+                let value = row.shift_remove(from).unwrap_or(Value::Null);
+                row.shift_insert(index, to.clone(), value);
+            }
+        }
+    }
+}
+
+/// Keep only the changes on common columns
+///
+/// Updates touching no common column are dropped; rows added or removed
+/// are kept whole.
+fn restrict_to_columns(diffs: Vec<RowDiff>, columns: &[String]) -> Vec<RowDiff> {
+    diffs
+        .into_iter()
+        .filter_map(|mut diff| {
+            // Updates with unknown changed columns (row hashes) are kept
+            if diff.change_type == "modified" && !diff.changed_columns.is_empty() {
+                diff.changed_columns.retain(|c| columns.contains(c));
+                if diff.changed_columns.is_empty() {
+                    return None;
+                }
+            }
+            Some(diff)
+        })
+        .collect()
+}
+
+/// Switch a table to a new row identity and fetch strategy, dropping the
+/// tracking state built for the previous ones
+fn restart_tracking(meta: &mut TableMeta, identity: RowIdentity, strategy: FetchStrategy) {
+    meta.identity = identity;
+    meta.strategy = strategy;
+    meta.reset_tracking();
+}
+
+/// Record the current column fingerprint of each table
+pub async fn take_column_fingerprints(
+    client: &tokio_postgres::Client,
+    tables: &[(String, String)],
+    metadata: &mut TableMetadata,
+) {
+    let Ok(fingerprints) = get_column_fingerprints(client, tables).await else {
+        return;
+    };
+    for (full_key, fingerprint) in fingerprints {
+        if let Some(meta) = metadata.get_mut(&full_key) {
+            meta.column_fingerprint = Some(fingerprint);
+        }
+    }
+}
+
+/// Detect column changes on watched tables and resynchronise their snapshots
+pub async fn collect_schema_changes(
+    tables: &[(String, String)],
+    snapshots: &TableSnapshots,
+    metadata: &mut TableMetadata,
+    options: &FetchOptions,
+    max_rows: usize,
+    client: &tokio_postgres::Client,
+) -> CycleResult {
+    let mut result = CycleResult::default();
+    let Ok(fingerprints) = get_column_fingerprints(client, tables).await else {
+        return result;
+    };
+
+    for (schema, table) in tables {
+        let full_key = format!("{}.{}", schema, table);
+        let (Some(meta), Some(fingerprint)) =
+            (metadata.get_mut(&full_key), fingerprints.get(&full_key))
+        else {
+            continue;
+        };
+        // Tables added since the previous cycle only get their baseline
+        match meta.column_fingerprint.replace(fingerprint.clone()) {
+            Some(previous) if previous != *fingerprint => {}
+            _ => continue,
+        }
+        let Ok(columns) = get_columns(client, schema, table).await else {
+            continue;
+        };
+
+        let changes = column_changes(&meta.columns, &columns);
+        let common = common_columns(&meta.columns, &columns);
+        meta.columns = columns;
+        let identity = get_row_identity(client, schema, table).await;
+        if let Ok(foreign_keys) = get_foreign_keys(client, schema, table).await {
+            meta.foreign_keys = foreign_keys;
+        }
+        result.add_schema_change(full_key.clone(), &changes);
+
+        // Rows cannot be matched across identities: start over from a new baseline
+        if identity != meta.identity {
+            let strategy =
+                resolve_strategy(client, schema, table, meta.kind, &identity, options).await;
+            restart_tracking(meta, identity, strategy);
+            snapshots.lock().unwrap().remove(&full_key);
+            take_table_snapshot(client, schema, table, meta, max_rows, snapshots).await;
+            continue;
+        }
+
+        // Diff the data on the columns that exist on both sides
+        let has_snapshot = {
+            let mut snapshots = snapshots.lock().unwrap();
+            match snapshots.get_mut(&full_key) {
+                Some(rows) => {
+                    rename_columns(rows, &changes);
+                    true
+                }
+                None => false,
+            }
+        };
+        if has_snapshot {
+            if meta.strategy == FetchStrategy::Xmin {
+                // A rewrite gives every row a new xmin: fetch the whole window
+                meta.watermark = None;
+            }
+            let diffs =
+                calculate_table_diffs(client, schema, table, meta, max_rows, snapshots).await;
+            let diffs = restrict_to_columns(diffs, &common);
+            result.count_diffs(&diffs);
+            result.push_table(full_key, diffs);
+        } else if !meta.fingerprints.is_empty() {
+            // Row hashes cover every column: start over with the new ones
+            meta.fingerprints.clear();
+            take_fingerprints(client, schema, table, meta, max_rows)
+                .await
+                .ok();
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(attnum: i16, name: &str, type_name: &str) -> Column {
+        Column {
+            attnum,
+            name: name.to_string(),
+            type_oid: 0,
+            type_name: type_name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_column_changes() {
+        let old = vec![
+            column(1, "id", "integer"),
+            column(2, "name", "text"),
+            column(3, "age", "integer"),
+            column(4, "legacy", "text"),
+        ];
+        let new = vec![
+            column(1, "id", "integer"),
+            column(2, "full_name", "text"),
+            column(3, "age", "bigint"),
+            column(5, "email", "text"),
+        ];
+
+        assert_eq!(
+            column_changes(&old, &new),
+            vec![
+                ColumnChange::Renamed {
+                    from: "name".to_string(),
+                    to: "full_name".to_string()
+                },
+                ColumnChange::TypeChanged {
+                    name: "age".to_string(),
+                    from: "integer".to_string(),
+                    to: "bigint".to_string()
+                },
+                ColumnChange::Dropped {
+                    name: "legacy".to_string()
+                },
+                ColumnChange::Added {
+                    name: "email".to_string(),
+                    type_name: "text".to_string()
+                },
+            ]
+        );
+        assert_eq!(common_columns(&old, &new), vec!["id", "full_name"]);
+    }
+
+    #[test]
+    fn test_restart_tracking_after_key_dropped() {
+        let key = RowIdentity {
+            strategy: crate::types::IdentityStrategy::PrimaryKey,
+            columns: vec!["id".to_string()],
+            types: vec!["integer".to_string()],
+        };
+        let mut meta = TableMeta::new(key, Vec::new(), FetchStrategy::RowHash);
+        meta.boundary = Some(vec!["100".to_string()]);
+        meta.last_key = Some(vec!["100".to_string()]);
+        meta.watermark = Some("700".to_string());
+        meta.xmin_horizon = Some("690".to_string());
+        meta.fingerprints
+            .insert(vec!["1".to_string()], "hash".to_string());
+
+        restart_tracking(&mut meta, RowIdentity::none(), FetchStrategy::Full);
+        assert!(meta.identity.columns.is_empty());
+        assert_eq!(meta.strategy, FetchStrategy::Full);
+        assert_eq!(meta.boundary, None);
+        assert_eq!(meta.last_key, None);
+        assert_eq!(meta.watermark, None);
+        assert_eq!(meta.xmin_horizon, None);
+        assert!(meta.fingerprints.is_empty());
+    }
+}
@@ -2,11 +2,19 @@
 
 use tokio::io::{AsyncBufReadExt, BufReader};
 
+use super::hashing::take_fingerprints;
+use super::incremental::{fetch_changed_rows, fetch_column_slice};
 use crate::constants::INPUT_CHANNEL_BUFFER;
-use crate::db::fetch_all_rows;
+use crate::db::{
+    fetch_rows, get_column_type, get_columns, get_content_hash, get_foreign_keys, get_partitions,
+    get_relation_kind, get_row_identity, get_xmin_watermark,
+};
 use crate::display::print_warning;
 use crate::input::select_tables_interactively;
-use crate::types::TableSnapshots;
+use crate::types::{
+    FetchOptions, FetchStrategy, RelationKind, RowData, RowIdentity, TableMeta, TableMetadata,
+    TableSnapshots,
+};
 
 /// Initial table selection
 pub async fn select_initial_tables(
@@ -14,29 +22,284 @@ pub async fn select_initial_tables(
     interactive: bool,
 ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
     if interactive {
-        let selected = select_tables_interactively(all_tables).await?;
-        if selected.is_empty() {
-            print_warning("No tables selected. Exiting.");
-            return Ok(Vec::new());
-        }
-        Ok(selected)
+        select_tables_interactively(all_tables).await
     } else {
         Ok(all_tables.to_vec())
     }
 }
 
+/// Load metadata (kind, row identity, columns, fetch strategy and foreign keys) for tables
+pub async fn load_metadata(
+    client: &tokio_postgres::Client,
+    tables: &[(String, String)],
+    options: &FetchOptions,
+) -> TableMetadata {
+    let mut metadata = TableMetadata::new();
+    for (schema, table) in tables {
+        let kind = get_relation_kind(client, schema, table)
+            .await
+            .unwrap_or_default();
+        let partitions = if kind == RelationKind::Partitioned {
+            get_partitions(client, schema, table)
+                .await
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let identity = get_row_identity(client, schema, table).await;
+        let columns = get_columns(client, schema, table).await.unwrap_or_default();
+        let strategy = resolve_strategy(client, schema, table, kind, &identity, options).await;
+        let foreign_keys = get_foreign_keys(client, schema, table)
+            .await
+            .unwrap_or_default();
+        metadata.insert(
+            format!("{}.{}", schema, table),
+            TableMeta {
+                kind,
+                partitions,
+                foreign_keys,
+                ..TableMeta::new(identity, columns, strategy)
+            },
+        );
+    }
+    metadata
+}
+
+/// Choose the fetch strategy for a table
+pub async fn resolve_strategy(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    kind: RelationKind,
+    identity: &RowIdentity,
+    options: &FetchOptions,
+) -> FetchStrategy {
+    let full_key = format!("{}.{}", schema, table);
+    let find = |pairs: &[(String, String)]| {
+        pairs
+            .iter()
+            .find(|(spec, _)| spec == table || *spec == full_key)
+            .map(|(_, column)| column.clone())
+    };
+
+    // Relations without tuple counters are re-queried in full
+    if !kind.has_counters() {
+        if find(&options.append_only).is_some() || find(&options.since_columns).is_some() {
+            print_warning(&format!(
+                "{} is not a table; it is re-queried in full each cycle.",
+                full_key
+            ));
+        }
+        return FetchStrategy::Full;
+    }
+
+    if let Some(column) = find(&options.append_only) {
+        if let Some(column_type) = resolve_column(client, schema, table, &column).await {
+            return FetchStrategy::Keyset {
+                column,
+                column_type,
+            };
+        }
+    } else if let Some(column) = find(&options.since_columns) {
+        // Updated rows are matched to the snapshot by key
+        if !identity.is_key() {
+            print_warning(&format!(
+                "{} has no key; --since-column needs one. Using full snapshots.",
+                full_key
+            ));
+        } else if let Some(column_type) = resolve_column(client, schema, table, &column).await {
+            return FetchStrategy::SinceColumn {
+                column,
+                column_type,
+            };
+        }
+    }
+
+    key_strategy(identity, options)
+}
+
+/// Choose between row hashes, xmin and full snapshots
+///
+/// Both row hashes and xmin match rows by key, so tables without one are
+/// always fetched in full.
+fn key_strategy(identity: &RowIdentity, options: &FetchOptions) -> FetchStrategy {
+    if options.row_hash && identity.is_key() {
+        FetchStrategy::RowHash
+    } else if options.incremental && identity.is_key() {
+        FetchStrategy::Xmin
+    } else {
+        FetchStrategy::Full
+    }
+}
+
+/// Look up the type of a strategy column, warning when it does not exist
+async fn resolve_column(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    column: &str,
+) -> Option<String> {
+    let column_type = get_column_type(client, schema, table, column)
+        .await
+        .ok()
+        .flatten();
+    if column_type.is_none() {
+        print_warning(&format!(
+            "Column '{}' not found on {}.{}. Using full snapshots.",
+            column, schema, table
+        ));
+    }
+    column_type
+}
+
 /// Take snapshots
 pub async fn take_snapshots(
     client: &tokio_postgres::Client,
     tables: &[(String, String)],
     snapshots: &TableSnapshots,
+    metadata: &mut TableMetadata,
+    max_rows: usize,
 ) {
     for (schema, table) in tables {
         let key = format!("{}.{}", schema, table);
-        if let Ok(rows) = fetch_all_rows(client, schema, table).await {
-            snapshots.lock().unwrap().insert(key, rows);
+        let Some(meta) = metadata.get_mut(&key) else {
+            continue;
+        };
+        take_table_snapshot(client, schema, table, meta, max_rows, snapshots).await;
+    }
+}
+
+/// Take the baseline of one table: its rows, or row hashes with that strategy
+pub async fn take_table_snapshot(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    meta: &mut TableMeta,
+    max_rows: usize,
+    snapshots: &TableSnapshots,
+) {
+    if !meta.kind.has_counters() {
+        meta.content_hash = get_content_hash(client, schema, table).await.ok();
+    }
+    if meta.strategy == FetchStrategy::RowHash {
+        take_fingerprints(client, schema, table, meta, max_rows)
+            .await
+            .ok();
+        return;
+    }
+    if let Ok(rows) = fetch_tracked_rows(client, schema, table, meta, max_rows, None).await {
+        snapshots
+            .lock()
+            .unwrap()
+            .insert(format!("{}.{}", schema, table), rows);
+    }
+}
+
+/// Fetch the tracked rows of a table in row identity order
+///
+/// With the xmin strategy and a `previous` snapshot, only rows changed since
+/// the previous fetch are read and merged into it. The since-column and
+/// append-only strategies return only the changed rows.
+pub async fn fetch_tracked_rows(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    meta: &mut TableMeta,
+    max_rows: usize,
+    previous: Option<&[RowData]>,
+) -> Result<Vec<RowData>, Box<dyn std::error::Error + Send + Sync>> {
+    // Taken before fetching so that concurrent commits are picked up next time
+    let watermark = match meta.strategy {
+        FetchStrategy::Xmin => Some(get_xmin_watermark(client).await?),
+        FetchStrategy::Full | FetchStrategy::RowHash => None,
+        FetchStrategy::SinceColumn { .. } | FetchStrategy::Keyset { .. } => {
+            return fetch_column_slice(client, schema, table, meta, max_rows, previous).await;
         }
+    };
+
+    let changed = match (previous, meta.watermark.take()) {
+        (Some(previous), Some(since)) => {
+            fetch_changed_rows(client, schema, table, meta, max_rows, previous, &since).await?
+        }
+        _ => None,
+    };
+    let rows = match changed {
+        Some(rows) => rows,
+        None => fetch_window(client, schema, table, meta, max_rows).await?,
+    };
+
+    meta.watermark = watermark;
+    Ok(rows)
+}
+
+/// Fetch all rows in the tracked window of a table
+///
+/// When the window was capped this cycle, rows of the previous snapshot past
+/// the new boundary are returned as well; only the first `max_rows` rows
+/// remain tracked.
+pub(super) async fn fetch_window(
+    client: &tokio_postgres::Client,
+    schema: &str,
+    table: &str,
+    meta: &mut TableMeta,
+    max_rows: usize,
+) -> Result<Vec<RowData>, Box<dyn std::error::Error + Send + Sync>> {
+    let boundary = meta.boundary.clone();
+    let mut rows = fetch_rows(
+        client,
+        schema,
+        table,
+        meta,
+        boundary.as_deref(),
+        None,
+        window_limit(max_rows),
+    )
+    .await?;
+
+    let keys: Vec<&Vec<String>> = rows.iter().map(|(key, _)| key).collect();
+    match cap_window(meta, &keys, max_rows) {
+        Some(previous_end) => {
+            rows = fetch_rows(client, schema, table, meta, Some(&previous_end), None, 0).await?;
+        }
+        None if max_rows > 0 => rows.truncate(max_rows),
+        None => {}
     }
+
+    Ok(rows.into_iter().map(|(_, row)| row).collect())
+}
+
+/// Row limit for fetching a tracked window (one past the cap, to notice it is exceeded)
+pub(super) fn window_limit(max_rows: usize) -> usize {
+    if max_rows == 0 {
+        0
+    } else {
+        max_rows + 1
+    }
+}
+
+/// Cap the tracked window of a table at `max_rows` rows
+///
+/// `order_keys` are the keys fetched up to the boundary, at most
+/// `max_rows + 1`. Once they reach past the cap, the boundary is set (or
+/// lowered) to the last key within it, so that inserts inside the window do
+/// not grow it. Returns the previous end of the window when the previous
+/// snapshot reaches past the cap: rows up to it must be fetched and compared
+/// once more, so that rows dropping out of the window are not reported as
+/// deleted.
+pub(super) fn cap_window(
+    meta: &mut TableMeta,
+    order_keys: &[&Vec<String>],
+    max_rows: usize,
+) -> Option<Vec<String>> {
+    if max_rows == 0 || order_keys.len() <= max_rows {
+        meta.last_key = order_keys.last().map(|key| (*key).clone());
+        return None;
+    }
+
+    let cap_key = order_keys[max_rows - 1].clone();
+    let previous_end = meta.last_key.replace(cap_key.clone());
+    meta.boundary = Some(cap_key);
+    previous_end.filter(|end| !order_keys[..max_rows].contains(&end))
 }
 
 /// Setup input channel
@@ -57,3 +320,100 @@ pub fn setup_input_channel(interactive: bool) -> tokio::sync::mpsc::Receiver<Str
 
     rx
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::RowIdentity;
+
+    fn keys(ids: &[&str]) -> Vec<Vec<String>> {
+        ids.iter().map(|id| vec![id.to_string()]).collect()
+    }
+
+    fn cap(meta: &mut TableMeta, ids: &[&str], max_rows: usize) -> Option<Vec<String>> {
+        let keys = keys(ids);
+        let refs: Vec<&Vec<String>> = keys.iter().collect();
+        cap_window(meta, &refs, max_rows)
+    }
+
+    fn meta() -> TableMeta {
+        TableMeta::new(RowIdentity::none(), Vec::new(), FetchStrategy::Full)
+    }
+
+    #[test]
+    fn test_window_within_cap() {
+        let mut meta = meta();
+        assert_eq!(cap(&mut meta, &["1", "2", "3"], 3), None);
+        assert_eq!(meta.boundary, None);
+        assert_eq!(meta.last_key, Some(vec!["3".to_string()]));
+
+        assert_eq!(cap(&mut meta, &["1", "2", "3", "4", "5"], 0), None);
+        assert_eq!(meta.boundary, None);
+        assert_eq!(window_limit(0), 0);
+        assert_eq!(window_limit(3), 4);
+    }
+
+    #[test]
+    fn test_window_capped() {
+        let mut meta = meta();
+        assert_eq!(cap(&mut meta, &["1", "2", "3", "4"], 3), None);
+        assert_eq!(meta.boundary, Some(vec!["3".to_string()]));
+        assert_eq!(meta.last_key, Some(vec!["3".to_string()]));
+
+        // Inserts inside the window lower the boundary instead of growing it
+        assert_eq!(
+            cap(&mut meta, &["0", "1", "2", "3"], 3),
+            Some(vec!["3".to_string()])
+        );
+        assert_eq!(meta.boundary, Some(vec!["2".to_string()]));
+        assert_eq!(meta.last_key, Some(vec!["2".to_string()]));
+    }
+
+    #[test]
+    fn test_window_previous_end_within_cap() {
+        let mut meta = meta();
+        meta.last_key = Some(vec!["2".to_string()]);
+
+        // Rows past the previous end were inserted since, so nothing drops out
+        assert_eq!(cap(&mut meta, &["1", "2", "3", "4"], 3), None);
+        assert_eq!(meta.boundary, Some(vec!["3".to_string()]));
+    }
+
+    #[test]
+    fn test_key_strategy() {
+        let key = RowIdentity {
+            strategy: crate::types::IdentityStrategy::PrimaryKey,
+            columns: vec!["id".to_string()],
+            types: vec!["integer".to_string()],
+        };
+        let options = |incremental, row_hash| FetchOptions {
+            incremental,
+            row_hash,
+            ..FetchOptions::default()
+        };
+
+        assert_eq!(
+            key_strategy(&key, &options(true, false)),
+            FetchStrategy::Xmin
+        );
+        assert_eq!(
+            key_strategy(&key, &options(true, true)),
+            FetchStrategy::RowHash
+        );
+        assert_eq!(
+            key_strategy(&key, &options(false, false)),
+            FetchStrategy::Full
+        );
+
+        // A table whose key was dropped falls back to full snapshots
+        let none = RowIdentity::none();
+        assert_eq!(
+            key_strategy(&none, &options(true, false)),
+            FetchStrategy::Full
+        );
+        assert_eq!(
+            key_strategy(&none, &options(false, true)),
+            FetchStrategy::Full
+        );
+    }
+}
@@ -3,8 +3,8 @@
 use std::collections::HashMap;
 
 use crate::constants::db::{DEBOUNCE_INTERVAL_MS, DEBOUNCE_MAX_ITERATIONS};
-use crate::db::{get_table_stats, has_stats_changes};
-use crate::types::TableStats;
+use crate::db::get_table_stats;
+use crate::types::{RowDiff, TableStats};
 
 /// Debounce statistics
 ///
@@ -35,6 +35,24 @@ pub async fn debounce_stats(
     final_stats
 }
 
+/// Detect changes in statistics
+///
+/// Besides new changes, counters that restarted and replaced storage count
+/// as changes, so that those cycles are debounced too.
+pub fn has_stats_changes(
+    current: &HashMap<(String, String), TableStats>,
+    previous: &HashMap<(String, String), TableStats>,
+) -> bool {
+    current.iter().any(|(key, stats)| match previous.get(key) {
+        Some(prev) => {
+            !detect_changes(stats, prev).is_empty()
+                || counters_reset(stats, prev)
+                || storage_replaced(stats, prev)
+        }
+        None => false,
+    })
+}
+
 /// Detect changes
 pub fn detect_changes(stats: &TableStats, prev: &TableStats) -> Vec<(&'static str, i64)> {
     let mut detected = Vec::new();
@@ -49,3 +67,311 @@ pub fn detect_changes(stats: &TableStats, prev: &TableStats) -> Vec<(&'static st
     }
     detected
 }
+
+/// Whether the storage of a table was replaced
+/// (TRUNCATE, but also VACUUM FULL, CLUSTER and table rewrites)
+pub fn storage_replaced(stats: &TableStats, prev: &TableStats) -> bool {
+    stats.filenodes != prev.filenodes
+}
+
+/// Whether a replaced storage may come from TRUNCATE
+///
+/// TRUNCATE zeroes the live tuple count, while a rewrite (VACUUM FULL,
+/// CLUSTER) keeps it, even right after every row was updated or inserted.
+pub fn truncate_suspected(stats: &TableStats, prev: &TableStats) -> bool {
+    storage_replaced(stats, prev) && stats.n_live_tup < prev.n_live_tup
+}
+
+/// Whether the counters of a table restarted since the previous statistics
+/// (`pg_stat_reset()`, a crash restart or a per-table reset)
+pub fn counters_reset(stats: &TableStats, prev: &TableStats) -> bool {
+    stats.epoch != prev.epoch
+        || stats.n_tup_ins < prev.n_tup_ins
+        || stats.n_tup_upd < prev.n_tup_upd
+        || stats.n_tup_del < prev.n_tup_del
+}
+
+/// Number of counted updates that can be key changes
+///
+/// An update of a key column shows as a removed and an added row while the
+/// counters show no matching delete or insert.
+pub fn unmatched_key_updates(detected: &[(&str, i64)], diffs: &[RowDiff]) -> usize {
+    let counted = |change_type: &str| {
+        detected
+            .iter()
+            .filter(|(t, _)| *t == change_type)
+            .map(|(_, n)| *n)
+            .sum::<i64>()
+    };
+    let diffed =
+        |diff_type: &str| diffs.iter().filter(|d| d.change_type == diff_type).count() as i64;
+
+    let updates = counted("UPDATE") - diffed("modified");
+    let added = diffed("added") - counted("INSERT");
+    let removed = diffed("removed") - counted("DELETE");
+    updates.min(added).min(removed).max(0) as usize
+}
+
+/// Explain counted changes that left no row diff
+///
+/// Tuple counters also count rolled-back changes, rows inserted and deleted
+/// again before being fetched and updates that changed nothing. Tables with
+/// a key report updates as such; on other tables an update shows as a
+/// removed and an added row. On partially tracked tables the difference
+/// cannot be told apart from changes outside the tracked rows.
+pub fn explain_discrepancies(
+    detected: &[(&str, i64)],
+    diffs: &[RowDiff],
+    keyed: bool,
+    fully_tracked: bool,
+) -> Vec<String> {
+    let counted = |change_type: &str| {
+        detected
+            .iter()
+            .filter(|(t, _)| *t == change_type)
+            .map(|(_, n)| *n)
+            .sum::<i64>()
+    };
+    let diffed =
+        |diff_type: &str| diffs.iter().filter(|d| d.change_type == diff_type).count() as i64;
+    let (inserts, updates, deletes) = (counted("INSERT"), counted("UPDATE"), counted("DELETE"));
+
+    let (missing_inserts, missing_updates, missing_deletes) = if keyed {
+        (
+            (inserts - diffed("added")).max(0),
+            (updates - diffed("modified")).max(0),
+            (deletes - diffed("removed")).max(0),
+        )
+    } else {
+        let added = (inserts + updates - diffed("added")).max(0);
+        let removed = (deletes + updates - diffed("removed")).max(0);
+        // Updates count on both sides: attribute what is missing on both to them
+        let updates = added.min(removed).min(updates);
+        (added - updates, updates, removed - updates)
+    };
+
+    if !fully_tracked {
+        let total = missing_inserts + missing_updates + missing_deletes;
+        return if total > 0 {
+            vec![format!(
+                "{} {} outside tracked rows or without effect",
+                total,
+                plural(total, "change")
+            )]
+        } else {
+            Vec::new()
+        };
+    }
+
+    let churn = missing_inserts.min(missing_deletes);
+    let mut notes = Vec::new();
+    if churn > 0 {
+        notes.push(format!(
+            "{} {} inserted and deleted again",
+            churn,
+            plural(churn, "row")
+        ));
+    }
+    if missing_inserts > churn {
+        let n = missing_inserts - churn;
+        notes.push(format!("{} rolled-back {}", n, plural(n, "insert")));
+    }
+    if missing_updates > 0 {
+        let n = missing_updates;
+        if keyed && diffs.iter().any(|d| d.change_type != "modified") {
+            // An update of a row inserted or deleted in the cycle is part of that diff
+            notes.push(format!(
+                "{} {} with no separate change (no-op, rolled back or on inserted/deleted rows)",
+                n,
+                plural(n, "update")
+            ));
+        } else {
+            notes.push(format!(
+                "{} no-op or rolled-back {}",
+                n,
+                plural(n, "update")
+            ));
+        }
+    }
+    if missing_deletes > churn {
+        let n = missing_deletes - churn;
+        notes.push(format!("{} rolled-back {}", n, plural(n, "delete")));
+    }
+    notes
+}
+
+/// Pluralize a noun by count
+fn plural(n: i64, noun: &str) -> String {
+    if n == 1 {
+        noun.to_string()
+    } else {
+        format!("{}s", noun)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(ins: i64, upd: i64, del: i64) -> TableStats {
+        TableStats {
+            n_tup_ins: ins,
+            n_tup_upd: upd,
+            n_tup_del: del,
+            n_live_tup: ins - del,
+            filenodes: Some("16384".to_string()),
+            horizon: "700".to_string(),
+            epoch: "2026-01-01 00:00:00+00".to_string(),
+        }
+    }
+
+    fn diff(change_type: &str) -> RowDiff {
+        RowDiff {
+            table: "public.users".to_string(),
+            pk_columns: vec!["id".to_string()],
+            pk_values: vec!["1".to_string()],
+            change_type: change_type.to_string(),
+            old_values: None,
+            new_values: None,
+            changed_columns: Vec::new(),
+            xid: None,
+        }
+    }
+
+    #[test]
+    fn test_explain_discrepancies() {
+        // Counts matching the diffs need no explanation
+        let diffs = vec![diff("added"), diff("modified")];
+        assert!(
+            explain_discrepancies(&[("INSERT", 1), ("UPDATE", 1)], &diffs, true, true).is_empty()
+        );
+
+        assert_eq!(
+            explain_discrepancies(&[("INSERT", 3), ("UPDATE", 2)], &[], true, true),
+            vec!["3 rolled-back inserts", "2 no-op or rolled-back updates"]
+        );
+        assert_eq!(
+            explain_discrepancies(
+                &[("INSERT", 2), ("DELETE", 1)],
+                &[diff("added")],
+                true,
+                true
+            ),
+            vec!["1 row inserted and deleted again"]
+        );
+        assert_eq!(
+            explain_discrepancies(
+                &[("INSERT", 1), ("UPDATE", 1)],
+                &[diff("added")],
+                true,
+                true
+            ),
+            vec![
+                "1 update with no separate change (no-op, rolled back or on inserted/deleted rows)"
+            ]
+        );
+        assert_eq!(
+            explain_discrepancies(&[("UPDATE", 4)], &[diff("modified")], true, false),
+            vec!["3 changes outside tracked rows or without effect"]
+        );
+
+        // Without a key, an update is a removed and an added row
+        let diffs = vec![diff("removed"), diff("added")];
+        assert!(explain_discrepancies(&[("UPDATE", 1)], &diffs, false, true).is_empty());
+        assert_eq!(
+            explain_discrepancies(&[("UPDATE", 2)], &diffs, false, true),
+            vec!["1 no-op or rolled-back update"]
+        );
+    }
+
+    #[test]
+    fn test_unmatched_key_updates() {
+        let diffs = vec![diff("removed"), diff("added")];
+        assert_eq!(unmatched_key_updates(&[("UPDATE", 1)], &diffs), 1);
+        // A real delete and insert
+        assert_eq!(
+            unmatched_key_updates(&[("INSERT", 1), ("DELETE", 1)], &diffs),
+            0
+        );
+        assert_eq!(
+            unmatched_key_updates(&[("UPDATE", 2)], &[diff("modified"), diff("added")]),
+            0
+        );
+    }
+
+    #[test]
+    fn test_counters_reset() {
+        let prev = stats(10, 5, 2);
+        assert!(!counters_reset(&stats(12, 5, 2), &prev));
+
+        // Counters below the previous ones restarted (per-table reset)
+        assert!(counters_reset(&stats(1, 5, 2), &prev));
+        assert!(counters_reset(&stats(10, 5, 0), &prev));
+
+        // A new epoch restarts all counters, even if they caught up meanwhile
+        let restarted = TableStats {
+            epoch: "2026-01-02 00:00:00+00".to_string(),
+            ..stats(12, 5, 2)
+        };
+        assert!(counters_reset(&restarted, &prev));
+    }
+
+    #[test]
+    fn test_storage_replaced() {
+        let prev = stats(10, 5, 2);
+        assert!(!storage_replaced(&stats(12, 5, 2), &prev));
+
+        let truncated = TableStats {
+            filenodes: Some("16390".to_string()),
+            ..prev.clone()
+        };
+        assert!(storage_replaced(&truncated, &prev));
+    }
+
+    #[test]
+    fn test_truncate_suspected() {
+        let prev = stats(10, 5, 2);
+        let rewritten = |n_live_tup: i64| TableStats {
+            filenodes: Some("16390".to_string()),
+            n_live_tup,
+            ..prev.clone()
+        };
+
+        // VACUUM FULL or CLUSTER right after every row was updated
+        let vacuumed = TableStats {
+            n_tup_upd: 13,
+            ..rewritten(8)
+        };
+        assert!(!truncate_suspected(&vacuumed, &prev));
+        assert!(!truncate_suspected(&rewritten(9), &prev));
+
+        // TRUNCATE, possibly followed by new rows
+        assert!(truncate_suspected(&rewritten(0), &prev));
+        assert!(truncate_suspected(&rewritten(3), &prev));
+
+        // Fewer live rows in the same storage are deletes
+        let deleted = TableStats {
+            n_live_tup: 0,
+            ..prev.clone()
+        };
+        assert!(!truncate_suspected(&deleted, &prev));
+    }
+
+    #[test]
+    fn test_has_stats_changes() {
+        let key = ("public".to_string(), "users".to_string());
+        let map = |stats: TableStats| HashMap::from([(key.clone(), stats)]);
+        let prev = map(stats(10, 5, 2));
+
+        assert!(!has_stats_changes(&map(stats(10, 5, 2)), &prev));
+        assert!(has_stats_changes(&map(stats(10, 6, 2)), &prev));
+        assert!(has_stats_changes(&map(stats(0, 0, 0)), &prev));
+
+        let truncated = TableStats {
+            filenodes: None,
+            ..stats(10, 5, 2)
+        };
+        assert!(has_stats_changes(&map(truncated), &prev));
+        assert!(!has_stats_changes(&map(stats(10, 6, 2)), &HashMap::new()));
+    }
+}
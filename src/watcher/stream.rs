@@ -0,0 +1,223 @@
+//! Row change stream handling
+//!
+//! Shared by sources that deliver individual row changes instead of
+//! requiring table snapshots (logical decoding and capture triggers).
+
+use crate::decoding::DecodedChange;
+use crate::diff::get_pk_value;
+use crate::error::Result;
+use crate::types::{Column, RowData, RowDiff};
+use crate::value::Value;
+
+use super::changes::CycleResult;
+use super::context::WatchContext;
+use super::logical::LogicalSource;
+use super::triggers::TriggerSource;
+
+/// Source delivering individual row changes
+pub enum StreamSource {
+    Logical(LogicalSource),
+    Triggers(TriggerSource),
+}
+
+impl StreamSource {
+    /// Collect changes received since the previous call
+    pub async fn collect_changes(&mut self, ctx: &WatchContext) -> Result<CycleResult> {
+        match self {
+            Self::Logical(source) => source.collect_changes(ctx).await,
+            Self::Triggers(source) => source.collect_changes(ctx).await,
+        }
+    }
+}
+
+/// Convert decoded changes on watched tables into a cycle result
+pub fn collect_stream_changes(ctx: &WatchContext, changes: Vec<DecodedChange>) -> CycleResult {
+    let mut result = CycleResult::default();
+
+    for mut change in changes {
+        let relation = format!("{}.{}", change.schema, change.table);
+        // Changes on partitions are shown on their partitioned table
+        let Some((full_key, meta)) = ctx.metadata.get_key_value(&relation).or_else(|| {
+            ctx.metadata
+                .iter()
+                .find(|(_, meta)| meta.partitions.contains(&relation))
+        }) else {
+            continue;
+        };
+        let full_key = full_key.clone();
+        // Stream sources deliver transactions in commit order
+        if let Some(xid) = change.xid {
+            result.add_transaction(xid, None);
+        }
+
+        if change.operation == "TRUNCATE" {
+            // Capture triggers send the row count estimate as `{"rows": n}`
+            let rows_lost = change
+                .old_values
+                .as_ref()
+                .and_then(|row| match row.get("rows") {
+                    Some(Value::Int(n)) => Some(*n),
+                    _ => None,
+                });
+            result.add_truncate(full_key, rows_lost);
+            continue;
+        }
+
+        // Trigger payloads list columns in JSON key order
+        for row in [&mut change.old_values, &mut change.new_values]
+            .into_iter()
+            .flatten()
+        {
+            order_columns(row, &meta.columns);
+        }
+        let pk_cols = &meta.identity.columns;
+
+        // Rows without identity cannot be tracked across an update
+        let diffs: Vec<RowDiff> = if pk_cols.is_empty() && change.operation == "UPDATE" {
+            split_keyless_update(change)
+                .into_iter()
+                .filter_map(|c| change_to_diff(c, &full_key, pk_cols))
+                .collect()
+        } else {
+            change_to_diff(change, &full_key, pk_cols)
+                .into_iter()
+                .collect()
+        };
+
+        if !diffs.is_empty() {
+            result.count_diffs(&diffs);
+            result.push_table(full_key, diffs);
+        }
+    }
+
+    result
+}
+
+/// Put the columns of a row in table definition order
+fn order_columns(row: &mut RowData, columns: &[Column]) {
+    row.sort_by_cached_key(|name, _| {
+        columns
+            .iter()
+            .position(|c| c.name == *name)
+            .unwrap_or(usize::MAX)
+    });
+}
+
+/// Split an update on a table without row identity into a delete and an insert
+fn split_keyless_update(change: DecodedChange) -> Vec<DecodedChange> {
+    let delete = DecodedChange {
+        operation: "DELETE".to_string(),
+        new_values: None,
+        ..change.clone()
+    };
+    let insert = DecodedChange {
+        operation: "INSERT".to_string(),
+        old_values: None,
+        ..change
+    };
+    vec![delete, insert]
+}
+
+/// Convert a decoded change into a row diff
+fn change_to_diff(change: DecodedChange, full_key: &str, pk_cols: &[String]) -> Option<RowDiff> {
+    let xid = change.xid;
+    let (change_type, old_values, new_values, changed_columns) = match change.operation.as_str() {
+        "INSERT" => {
+            let new = change.new_values?;
+            let cols = new.keys().cloned().collect();
+            ("added", None, Some(new), cols)
+        }
+        "DELETE" => match change.old_values {
+            Some(old) => {
+                let cols = old.keys().cloned().collect();
+                ("removed", Some(old), None, cols)
+            }
+            // Tables without replica identity send deletes without row data
+            None => ("removed", None, None, Vec::new()),
+        },
+        _ => {
+            let new = change.new_values?;
+            // Logical decoding only sends a full old image with REPLICA IDENTITY FULL,
+            // otherwise just the old key when the key changed
+            let old = change.old_values;
+            let cols: Vec<String> = new
+                .iter()
+                .filter(|(k, v)| old.as_ref().and_then(|old| old.get(*k)) != Some(*v))
+                .map(|(k, _)| k.clone())
+                .collect();
+            if cols.is_empty() {
+                return None;
+            }
+            ("modified", old, Some(new), cols)
+        }
+    };
+
+    // A delete without row data cannot be identified
+    let row = new_values.as_ref().or(old_values.as_ref());
+    let pk_cols = if row.is_some() { pk_cols } else { &[] };
+    let pk_values = row
+        .map(|row| get_pk_value(row, pk_cols))
+        .unwrap_or_default();
+
+    Some(RowDiff {
+        table: full_key.to_string(),
+        pk_columns: pk_cols.to_vec(),
+        pk_values,
+        change_type: change_type.to_string(),
+        old_values,
+        new_values,
+        changed_columns,
+        xid,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoding::parse_change;
+
+    fn diff(line: &str, pk_cols: &[&str]) -> Option<RowDiff> {
+        let pk_cols: Vec<String> = pk_cols.iter().map(|c| c.to_string()).collect();
+        change_to_diff(parse_change(line).unwrap(), "public.t", &pk_cols)
+    }
+
+    #[test]
+    fn test_delete_without_tuple_data() {
+        let d = diff("table public.t: DELETE: (no-tuple-data)", &["id"]).unwrap();
+        assert_eq!(d.change_type, "removed");
+        assert!(d.old_values.is_none());
+        assert!(d.pk_columns.is_empty());
+        assert!(d.pk_values.is_empty());
+
+        let d = diff("table public.t: DELETE: (no-tuple-data)", &[]).unwrap();
+        assert_eq!(d.change_type, "removed");
+    }
+
+    #[test]
+    fn test_update_with_old_key() {
+        let line = "table public.t: UPDATE: old-key: id[integer]:1 \
+                    new-tuple: id[integer]:2 name[text]:'a'";
+        let d = diff(line, &["id"]).unwrap();
+        assert_eq!(d.change_type, "modified");
+        assert_eq!(d.pk_values, vec!["2"]);
+        assert_eq!(d.old_values.as_ref().unwrap()["id"], Value::Int(1));
+        // The old value of non-key columns is unknown
+        assert_eq!(d.changed_columns, vec!["id", "name"]);
+
+        let line = "table public.t: UPDATE: id[integer]:2 name[text]:'a'";
+        let d = diff(line, &["id"]).unwrap();
+        assert!(d.old_values.is_none());
+        assert_eq!(d.changed_columns, vec!["id", "name"]);
+    }
+
+    #[test]
+    fn test_update_with_full_old_image() {
+        let line = "table public.t: UPDATE: old-key: id[integer]:1 name[text]:'a' \
+                    new-tuple: id[integer]:1 name[text]:'b'";
+        assert_eq!(diff(line, &["id"]).unwrap().changed_columns, vec!["name"]);
+
+        let line = "table public.t: UPDATE: old-key: id[integer]:1 name[text]:'a' \
+                    new-tuple: id[integer]:1 name[text]:'a'";
+        assert!(diff(line, &["id"]).is_none());
+    }
+}
@@ -0,0 +1,68 @@
+//! Transaction attribution for polled changes
+//!
+//! Row diffs found by polling carry no transaction; the `xmin` of each row
+//! still present tells which transaction last wrote it.
+
+use crate::db::fetch_row_transactions;
+use crate::types::TableMetadata;
+
+use super::changes::CycleResult;
+
+/// Tag row diffs with the transaction that wrote them
+///
+/// Only rows that still exist can be attributed; removed rows keep no
+/// transaction. Transactions are ordered by commit time when
+/// `track_commit_timestamp` is on, by transaction id (start order) otherwise,
+/// in which case the result is marked unordered.
+pub async fn assign_transactions(
+    result: &mut CycleResult,
+    tables: &[(String, String)],
+    metadata: &TableMetadata,
+    client: &tokio_postgres::Client,
+) {
+    let mut found = Vec::new();
+
+    for (schema, table) in tables {
+        let full_key = format!("{}.{}", schema, table);
+        let Some(meta) = metadata.get(&full_key) else {
+            continue;
+        };
+        // Rows of views and foreign tables have no xmin
+        if !meta.kind.has_counters() {
+            continue;
+        }
+        let keys: Vec<Vec<String>> = result
+            .diffs
+            .iter()
+            .filter(|d| d.table == full_key && d.new_values.is_some())
+            .map(|d| d.pk_values.clone())
+            .collect();
+        if keys.is_empty() {
+            continue;
+        }
+        let Ok(transactions) = fetch_row_transactions(client, schema, table, meta, &keys).await
+        else {
+            continue;
+        };
+
+        for diff in result
+            .diffs
+            .iter_mut()
+            .filter(|d| d.table == full_key && d.new_values.is_some())
+        {
+            if let Some(&(xid, committed_at)) = transactions.get(&diff.pk_values) {
+                diff.xid = Some(xid);
+                found.push((committed_at, xid));
+            }
+        }
+    }
+
+    found.sort();
+    found.dedup();
+    if found.len() > 1 && found.iter().any(|(committed_at, _)| committed_at.is_none()) {
+        result.unordered = true;
+    }
+    for (committed_at, xid) in found {
+        result.add_transaction(xid, committed_at);
+    }
+}
@@ -0,0 +1,51 @@
+//! Capture trigger change source
+
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::db::{get_audit_change, is_capture_installed, listen_capture, prune_audit_log};
+use crate::decoding::{parse_capture_payload, CapturePayload};
+use crate::error::{Result, TableTraceError};
+
+use super::changes::CycleResult;
+use super::context::WatchContext;
+use super::stream::collect_stream_changes;
+
+/// Change source fed by `pg_notify` calls from the capture triggers
+pub struct TriggerSource {
+    notifications: UnboundedReceiver<String>,
+}
+
+impl TriggerSource {
+    /// Check the capture schema exists, start listening and prune unread changes
+    pub async fn start(
+        client: &tokio_postgres::Client,
+        notifications: UnboundedReceiver<String>,
+    ) -> Result<Self> {
+        if !is_capture_installed(client).await? {
+            return Err(TableTraceError::CaptureNotInstalled);
+        }
+        listen_capture(client).await?;
+        prune_audit_log(client).await?;
+
+        Ok(Self { notifications })
+    }
+
+    /// Collect changes notified since the previous call
+    pub async fn collect_changes(&mut self, ctx: &WatchContext) -> Result<CycleResult> {
+        let mut changes = Vec::new();
+
+        while let Ok(payload) = self.notifications.try_recv() {
+            match parse_capture_payload(&payload) {
+                Some(CapturePayload::Change(change)) => changes.push(*change),
+                Some(CapturePayload::AuditId(id)) => {
+                    if let Some(change) = get_audit_change(&ctx.client, id).await? {
+                        changes.push(change);
+                    }
+                }
+                None => {}
+            }
+        }
+
+        Ok(collect_stream_changes(ctx, changes))
+    }
+}